@@ -0,0 +1,600 @@
+//! Common surface shared by every art and the registry the page uses to
+//! discover and build them.
+
+use crate::clock::{Clock, TimeMode};
+use crate::error::Error;
+use crate::helpers::*;
+use crate::layout::{Fit, Layout};
+use crate::mouse::Mouse;
+use crate::render_loop::RenderLoop;
+use crate::surface::Surface;
+use crate::*;
+use js_sys::{Array, Object, Reflect};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{HtmlCanvasElement, OffscreenCanvas};
+
+/// Behaviour every art exposes to the page.
+pub trait Art {
+    /// Resizes the art to `width` x `height` CSS pixels, rendering the
+    /// backing store at `pixel_ratio` device pixels per CSS pixel.
+    fn resize(&mut self, width: f32, height: f32, pixel_ratio: f32);
+
+    /// Placement of the figure for point-cloud arts, `None` for arts that
+    /// fill the whole canvas.
+    fn layout_mut(&mut self) -> Option<&mut Layout> {
+        None
+    }
+
+    fn set_fit(&mut self, fit: Fit) {
+        if let Some(layout) = self.layout_mut() {
+            layout.fit = fit;
+        }
+    }
+
+    fn set_scale(&mut self, scale: f32) {
+        if let Some(layout) = self.layout_mut() {
+            layout.scale = scale;
+        }
+    }
+
+    /// Moves the figure's centre `x`, `y` CSS pixels from the canvas centre.
+    fn set_offset(&mut self, x: f32, y: f32) {
+        if let Some(layout) = self.layout_mut() {
+            layout.offset_x = x;
+            layout.offset_y = y;
+        }
+    }
+
+    fn set_color(&mut self, r: f32, g: f32, b: f32);
+
+    /// Pointer state for arts whose shaders read the `mouse` uniform,
+    /// `None` for arts that handle the pointer themselves or ignore it.
+    fn mouse_mut(&mut self) -> Option<&mut Mouse> {
+        None
+    }
+
+    /// Pointer moved to `x`, `y` CSS pixels from the canvas' top left corner.
+    fn pointer_move(&mut self, x: f32, y: f32) {
+        if let Some(mouse) = self.mouse_mut() {
+            mouse.move_to(x, y);
+        }
+    }
+
+    /// Button pressed or finger put down at `x`, `y`.
+    fn pointer_down(&mut self, x: f32, y: f32) {
+        if let Some(mouse) = self.mouse_mut() {
+            mouse.press(x, y);
+        }
+    }
+
+    fn pointer_up(&mut self, x: f32, y: f32) {
+        if let Some(mouse) = self.mouse_mut() {
+            mouse.release(x, y);
+        }
+    }
+
+    /// Pointer left the canvas.
+    fn pointer_leave(&mut self) {
+        if let Some(mouse) = self.mouse_mut() {
+            mouse.leave();
+        }
+    }
+
+    /// Draws a frame. `timestamp` is in milliseconds on the `performance.now()`
+    /// timeline, e.g. the one `requestAnimationFrame` passes; `None` reads the
+    /// current time. Does nothing while the WebGL context is lost.
+    fn draw(&mut self, timestamp: Option<f64>) -> Result<(), Error>;
+
+    /// JS listeners the last `draw` has to call, run by `draw_shared` once
+    /// the art is no longer borrowed so they can call back into it.
+    fn take_notifications(&mut self) -> Option<Box<dyn FnOnce()>> {
+        None
+    }
+
+    fn clock(&self) -> &Clock;
+
+    fn clock_mut(&mut self) -> &mut Clock;
+
+    fn set_time_mode(&mut self, mode: TimeMode) {
+        self.clock_mut().set_mode(mode);
+    }
+
+    /// Playback speed multiplier, 1.0 being the original speed, at least 0.
+    fn set_speed(&mut self, speed: f64) {
+        self.clock_mut().set_speed(speed);
+    }
+
+    /// Freezes the animation time; `draw` keeps rendering the same frame.
+    fn pause(&mut self) {
+        self.clock_mut().pause();
+    }
+
+    fn resume(&mut self) {
+        self.clock_mut().resume();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.clock().is_paused()
+    }
+
+    fn get_time(&self) -> f64 {
+        self.clock().time()
+    }
+
+    /// Seeks to animation time `time`, in the units `get_time` reports.
+    fn set_time(&mut self, time: f64) {
+        self.clock_mut().set_time(time);
+    }
+
+    /// Seed of the art's random layout, `None` for arts without one.
+    fn seed(&self) -> Option<u32> {
+        None
+    }
+
+    /// Rebuilds the art's random layout from `seed`, keeping the current
+    /// time. Does nothing on arts without one.
+    fn reseed(&mut self, _seed: u32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Deletes the art's GPU objects; drawing afterwards is a no-op.
+    fn release(&mut self) {}
+}
+
+/// An art shared between its JS object and its animation loop.
+pub(crate) type SharedArt = Rc<RefCell<dyn Art>>;
+
+/// Draws `art`, then calls the listeners the frame raised.
+pub(crate) fn draw_shared(art: &RefCell<dyn Art>, timestamp: Option<f64>) -> Result<(), Error> {
+    let result = art.borrow_mut().draw(timestamp);
+    let notifications = art.borrow_mut().take_notifications();
+    if let Some(notify) = notifications {
+        notify();
+    }
+    result
+}
+
+/// Declares the `#[wasm_bindgen]` class `$art` around a shared `$inner` art,
+/// built by `$build(surface, width, height)`, and exports the `Art` methods
+/// and an animation loop on it, so every class keeps the same JS surface
+/// without repeating it in each module.
+macro_rules! art_class {
+    ($(#[$attr:meta])* $art:ident($inner:ty), $build:expr) => {
+        $(#[$attr])*
+        #[wasm_bindgen]
+        pub struct $art {
+            art: std::rc::Rc<std::cell::RefCell<$inner>>,
+            render_loop: $crate::render_loop::RenderLoop,
+        }
+
+        impl $art {
+            fn from_surface(
+                surface: $crate::surface::Surface,
+                width: u32,
+                height: u32,
+            ) -> Result<$art, $crate::error::Error> {
+                let art: $inner = ($build)(surface, width, height)?;
+                Ok($art {
+                    art: std::rc::Rc::new(std::cell::RefCell::new(art)),
+                    render_loop: $crate::render_loop::RenderLoop::default(),
+                })
+            }
+
+            /// Builds the art for the registry.
+            pub(crate) fn build(
+                surface: $crate::surface::Surface,
+                width: u32,
+                height: u32,
+            ) -> Result<$crate::art::SharedArt, $crate::error::Error> {
+                let art: $inner = ($build)(surface, width, height)?;
+                Ok(std::rc::Rc::new(std::cell::RefCell::new(art)))
+            }
+        }
+
+        #[wasm_bindgen]
+        impl $art {
+            /// Builds the art on the page's `#canvas` element.
+            #[wasm_bindgen(constructor)]
+            pub fn new(width: u32, height: u32) -> Result<$art, $crate::error::Error> {
+                $art::from_surface($crate::helpers::default_canvas()?.into(), width, height)
+            }
+
+            pub fn with_canvas(
+                canvas: web_sys::HtmlCanvasElement,
+                width: u32,
+                height: u32,
+            ) -> Result<$art, $crate::error::Error> {
+                $art::from_surface(canvas.into(), width, height)
+            }
+
+            /// Builds the art on the first canvas matching the CSS `selector`.
+            pub fn with_selector(
+                selector: &str,
+                width: u32,
+                height: u32,
+            ) -> Result<$art, $crate::error::Error> {
+                $art::from_surface($crate::helpers::find_canvas(selector)?.into(), width, height)
+            }
+
+            /// Builds the art on an `OffscreenCanvas`, e.g. inside a worker.
+            pub fn with_offscreen(
+                canvas: web_sys::OffscreenCanvas,
+                width: u32,
+                height: u32,
+            ) -> Result<$art, $crate::error::Error> {
+                $art::from_surface(canvas.into(), width, height)
+            }
+
+            pub fn resize(&self, width: f32, height: f32, pixel_ratio: f32) {
+                $crate::art::Art::resize(&mut *self.art.borrow_mut(), width, height, pixel_ratio);
+            }
+
+            pub fn set_color(&self, r: f32, g: f32, b: f32) {
+                $crate::art::Art::set_color(&mut *self.art.borrow_mut(), r, g, b);
+            }
+
+            pub fn pointer_move(&self, x: f32, y: f32) {
+                $crate::art::Art::pointer_move(&mut *self.art.borrow_mut(), x, y);
+            }
+
+            pub fn pointer_down(&self, x: f32, y: f32) {
+                $crate::art::Art::pointer_down(&mut *self.art.borrow_mut(), x, y);
+            }
+
+            pub fn pointer_up(&self, x: f32, y: f32) {
+                $crate::art::Art::pointer_up(&mut *self.art.borrow_mut(), x, y);
+            }
+
+            pub fn pointer_leave(&self) {
+                $crate::art::Art::pointer_leave(&mut *self.art.borrow_mut());
+            }
+
+            pub fn draw(&self, timestamp: Option<f64>) -> Result<(), $crate::error::Error> {
+                $crate::art::draw_shared(&*self.art, timestamp)
+            }
+
+            pub fn set_time_mode(&self, mode: $crate::clock::TimeMode) {
+                $crate::art::Art::set_time_mode(&mut *self.art.borrow_mut(), mode);
+            }
+
+            pub fn set_speed(&self, speed: f64) {
+                $crate::art::Art::set_speed(&mut *self.art.borrow_mut(), speed);
+            }
+
+            pub fn pause(&self) {
+                $crate::art::Art::pause(&mut *self.art.borrow_mut());
+            }
+
+            pub fn resume(&self) {
+                $crate::art::Art::resume(&mut *self.art.borrow_mut());
+            }
+
+            pub fn is_paused(&self) -> bool {
+                $crate::art::Art::is_paused(&*self.art.borrow())
+            }
+
+            pub fn get_time(&self) -> f64 {
+                $crate::art::Art::get_time(&*self.art.borrow())
+            }
+
+            pub fn set_time(&self, time: f64) {
+                $crate::art::Art::set_time(&mut *self.art.borrow_mut(), time);
+            }
+
+            pub fn seed(&self) -> Option<u32> {
+                $crate::art::Art::seed(&*self.art.borrow())
+            }
+
+            pub fn reseed(&self, seed: u32) -> Result<(), $crate::error::Error> {
+                $crate::art::Art::reseed(&mut *self.art.borrow_mut(), seed)
+            }
+
+            /// Draws the art on every animation frame until `stop` or `destroy`.
+            pub fn start(&self) {
+                self.render_loop.start(self.art.clone());
+            }
+
+            /// Caps the frame rate of the loop started by `start`, 0 for no cap.
+            pub fn set_max_fps(&self, max_fps: f64) {
+                self.render_loop.set_max_fps(max_fps);
+            }
+
+            pub fn is_running(&self) -> bool {
+                self.render_loop.is_running()
+            }
+
+            /// Cancels the pending animation frame, if the loop is running.
+            pub fn stop(&self) {
+                self.render_loop.stop();
+            }
+
+            /// Stops the loop and deletes the art's GPU objects. Also done on
+            /// drop, this only makes it happen before JS gets around to
+            /// calling `free`.
+            pub fn destroy(&self) {
+                self.render_loop.stop();
+                $crate::art::Art::release(&mut *self.art.borrow_mut());
+            }
+        }
+    };
+}
+
+/// Exports the layout setters on a point-cloud art class.
+macro_rules! layout_bindings {
+    ($art:ident) => {
+        #[wasm_bindgen]
+        impl $art {
+            pub fn set_fit(&self, fit: $crate::layout::Fit) {
+                $crate::art::Art::set_fit(&mut *self.art.borrow_mut(), fit);
+            }
+
+            pub fn set_scale(&self, scale: f32) {
+                $crate::art::Art::set_scale(&mut *self.art.borrow_mut(), scale);
+            }
+
+            pub fn set_offset(&self, x: f32, y: f32) {
+                $crate::art::Art::set_offset(&mut *self.art.borrow_mut(), x, y);
+            }
+        }
+    };
+}
+
+type Builder = fn(Surface, u32, u32) -> Result<SharedArt, Error>;
+
+struct ArtEntry {
+    id: &'static str,
+    reference: Option<&'static str>,
+    text: Option<&'static str>,
+    color: [f32; 3],
+    build: Builder,
+}
+
+const ARTS: &[ArtEntry] = &[
+    ArtEntry {
+        id: "Jellyfish",
+        reference: Some("https://x.com/yuruyurau/status/1865420201086636376"),
+        text: None,
+        color: [0.0, 1.0, 1.0],
+        build: JellyFish::build,
+    },
+    ArtEntry {
+        id: "Nudibranch",
+        reference: Some("https://x.com/yuruyurau/status/1866142306119885279"),
+        text: None,
+        color: [0.0, 1.0, 1.0],
+        build: Nudibranch::build,
+    },
+    ArtEntry {
+        id: "Heartbeat",
+        reference: None,
+        text: Some(
+            r#"<span style="color: rgb(255, 0.0, 74);">For my dearest <strong>Ghasedak</strong></span>"#,
+        ),
+        color: [1.0, 0.0, 0.29],
+        build: HeartController::build,
+    },
+    ArtEntry {
+        id: "PlanetaryTimer",
+        reference: Some("https://x.com/YoheiNishitsuji/status/1908486028018753622"),
+        text: None,
+        color: [0.0, 0.0, 0.0],
+        build: PlanetaryTimer::build,
+    },
+    ArtEntry {
+        id: "ChristmasTree",
+        reference: Some("https://x.com/YoheiNishitsuji/status/2004007970242547892"),
+        text: None,
+        color: [1.0, 1.0, 1.0],
+        build: ChristmasTree::build,
+    },
+    ArtEntry {
+        id: "Golfed1",
+        reference: Some("https://x.com/XorDev/status/2015813875833225715"),
+        text: None,
+        color: [1.0, 1.0, 1.0],
+        build: Golfed1::build,
+    },
+    ArtEntry {
+        id: "Eclipse",
+        reference: Some("https://x.com/XorDev/status/2016909652387123492"),
+        text: None,
+        color: [1.0, 1.0, 1.0],
+        build: Eclipse::build,
+    },
+];
+
+fn find_art(id: &str) -> Result<&'static ArtEntry, Error> {
+    ARTS.iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| Error::UnknownArt { id: id.to_string() })
+}
+
+/// Lists the available arts as `{ id, reference, text, color }` objects.
+#[wasm_bindgen]
+pub fn list_arts() -> Array {
+    ARTS.iter()
+        .map(|entry| {
+            let info = Object::new();
+            let color: Array = entry.color.iter().map(|&c| JsValue::from(c)).collect();
+            let _ = Reflect::set(&info, &"id".into(), &entry.id.into());
+            let _ = Reflect::set(&info, &"reference".into(), &entry.reference.into());
+            let _ = Reflect::set(&info, &"text".into(), &entry.text.into());
+            let _ = Reflect::set(&info, &"color".into(), &color);
+            JsValue::from(info)
+        })
+        .collect()
+}
+
+/// Builds the art registered under `id` on the page's `#canvas` element.
+#[wasm_bindgen]
+pub fn create_art(id: &str, width: u32, height: u32) -> Result<ArtHandle, Error> {
+    create_art_with_canvas(id, default_canvas()?, width, height)
+}
+
+#[wasm_bindgen]
+pub fn create_art_with_canvas(
+    id: &str,
+    canvas: HtmlCanvasElement,
+    width: u32,
+    height: u32,
+) -> Result<ArtHandle, Error> {
+    build_art(id, canvas.into(), width, height)
+}
+
+/// Builds the art registered under `id` on the first canvas matching `selector`.
+#[wasm_bindgen]
+pub fn create_art_with_selector(
+    id: &str,
+    selector: &str,
+    width: u32,
+    height: u32,
+) -> Result<ArtHandle, Error> {
+    create_art_with_canvas(id, find_canvas(selector)?, width, height)
+}
+
+/// Builds the art registered under `id` on an `OffscreenCanvas`, e.g. inside a worker.
+#[wasm_bindgen]
+pub fn create_art_with_offscreen(
+    id: &str,
+    canvas: OffscreenCanvas,
+    width: u32,
+    height: u32,
+) -> Result<ArtHandle, Error> {
+    build_art(id, canvas.into(), width, height)
+}
+
+pub(crate) fn build_art(
+    id: &str,
+    surface: Surface,
+    width: u32,
+    height: u32,
+) -> Result<ArtHandle, Error> {
+    let entry = find_art(id)?;
+    Ok(ArtHandle {
+        id: entry.id,
+        art: (entry.build)(surface, width, height)?,
+        render_loop: RenderLoop::default(),
+    })
+}
+
+/// An art built through the registry, whatever its concrete type. It can be
+/// drawn by the page or, after `start`, by its own animation loop.
+#[wasm_bindgen]
+pub struct ArtHandle {
+    id: &'static str,
+    art: SharedArt,
+    render_loop: RenderLoop,
+}
+
+#[wasm_bindgen]
+impl ArtHandle {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32, pixel_ratio: f32) {
+        self.art.borrow_mut().resize(width, height, pixel_ratio);
+    }
+
+    /// Fit, scale and offset only affect the point-cloud arts.
+    pub fn set_fit(&mut self, fit: Fit) {
+        self.art.borrow_mut().set_fit(fit);
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.art.borrow_mut().set_scale(scale);
+    }
+
+    pub fn set_offset(&mut self, x: f32, y: f32) {
+        self.art.borrow_mut().set_offset(x, y);
+    }
+
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.art.borrow_mut().set_color(r, g, b);
+    }
+
+    /// Pointer position in CSS pixels from the canvas' top left corner.
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        self.art.borrow_mut().pointer_move(x, y);
+    }
+
+    pub fn pointer_down(&mut self, x: f32, y: f32) {
+        self.art.borrow_mut().pointer_down(x, y);
+    }
+
+    pub fn pointer_up(&mut self, x: f32, y: f32) {
+        self.art.borrow_mut().pointer_up(x, y);
+    }
+
+    pub fn pointer_leave(&mut self) {
+        self.art.borrow_mut().pointer_leave();
+    }
+
+    pub fn draw(&mut self, timestamp: Option<f64>) -> Result<(), Error> {
+        draw_shared(&self.art, timestamp)
+    }
+
+    pub fn set_time_mode(&mut self, mode: TimeMode) {
+        self.art.borrow_mut().set_time_mode(mode);
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.art.borrow_mut().set_speed(speed);
+    }
+
+    pub fn pause(&mut self) {
+        self.art.borrow_mut().pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.art.borrow_mut().resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.art.borrow().is_paused()
+    }
+
+    pub fn get_time(&self) -> f64 {
+        self.art.borrow().get_time()
+    }
+
+    pub fn set_time(&mut self, time: f64) {
+        self.art.borrow_mut().set_time(time);
+    }
+
+    /// Seed of the art's random layout, undefined for arts without one.
+    pub fn seed(&self) -> Option<u32> {
+        self.art.borrow().seed()
+    }
+
+    pub fn reseed(&mut self, seed: u32) -> Result<(), Error> {
+        self.art.borrow_mut().reseed(seed)
+    }
+
+    /// Draws the art on every animation frame until `stop` or `destroy`.
+    pub fn start(&mut self) {
+        self.render_loop.start(self.art.clone());
+    }
+
+    /// Caps the frame rate of the loop started by `start`, 0 for no cap.
+    pub fn set_max_fps(&mut self, max_fps: f64) {
+        self.render_loop.set_max_fps(max_fps);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.render_loop.is_running()
+    }
+
+    /// Cancels the pending animation frame, if the loop is running.
+    pub fn stop(&mut self) {
+        self.render_loop.stop();
+    }
+
+    /// Stops the loop and deletes the art's GPU objects.
+    pub fn destroy(&mut self) {
+        self.render_loop.stop();
+        self.art.borrow_mut().release();
+    }
+}
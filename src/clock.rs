@@ -0,0 +1,141 @@
+//! Animation time of an art, driven by the wall clock or by a fixed step per
+//! drawn frame.
+
+use crate::helpers::*;
+use wasm_bindgen::prelude::*;
+
+/// Length of a frame on the 60 Hz display the per-frame steps were tuned on.
+pub const FRAME_MS: f64 = 1000.0 / 60.0;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeMode {
+    /// Time follows `performance.now()` or the timestamp given to `draw`, so
+    /// the animation runs at the same speed on any refresh rate.
+    WallClock,
+    /// Time advances by a fixed step on every `draw`, whatever the elapsed time.
+    FixedStep,
+}
+
+pub struct Clock {
+    mode: TimeMode,
+    /// Animation time per 60 Hz frame.
+    step: f64,
+    speed: f64,
+    time: f64,
+    last_timestamp: Option<f64>,
+    paused: bool,
+}
+
+impl Clock {
+    pub fn new(step: f64) -> Self {
+        Clock {
+            mode: TimeMode::WallClock,
+            step,
+            speed: 1.0,
+            time: 0.0,
+            last_timestamp: None,
+            paused: false,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: TimeMode) {
+        self.mode = mode;
+        self.last_timestamp = None;
+    }
+
+    /// Playback speed multiplier, 1.0 being the original speed. Time doesn't
+    /// run backwards, negative speeds stop it.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Continues from the paused time, without jumping over the pause.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.rebase();
+    }
+
+    /// Forgets the last timestamp, so time spent not drawing isn't counted.
+    pub fn rebase(&mut self) {
+        self.last_timestamp = None;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advances the clock for a frame drawn at `timestamp` milliseconds
+    /// (`performance.now()` when `None`) and returns the new time.
+    pub fn tick(&mut self, timestamp: Option<f64>) -> f64 {
+        if self.paused {
+            return self.time;
+        }
+
+        match self.mode {
+            TimeMode::FixedStep => {
+                self.time += self.step * self.speed;
+            }
+            TimeMode::WallClock => {
+                let timestamp = timestamp.unwrap_or_else(now);
+                if let Some(last) = self.last_timestamp {
+                    let elapsed = (timestamp - last).max(0.0);
+                    self.time += elapsed / FRAME_MS * self.step * self.speed;
+                }
+                self.last_timestamp = Some(timestamp);
+            }
+        }
+        self.time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_clock_follows_the_timestamps() {
+        let mut clock = Clock::new(FRAME_MS);
+        assert_eq!(clock.tick(Some(1000.0)), 0.0);
+        // Whatever the frame rate, a second is a second
+        assert_eq!(clock.tick(Some(1500.0)), 500.0);
+        assert_eq!(clock.tick(Some(2000.0)), 1000.0);
+        clock.set_speed(2.0);
+        assert_eq!(clock.tick(Some(2250.0)), 1500.0);
+        // Timestamps going back don't run time backwards
+        assert_eq!(clock.tick(Some(2000.0)), 1500.0);
+    }
+
+    #[test]
+    fn fixed_step_ignores_the_timestamps() {
+        let mut clock = Clock::new(0.5);
+        clock.set_mode(TimeMode::FixedStep);
+        clock.tick(Some(0.0));
+        clock.tick(Some(1000.0));
+        assert_eq!(clock.time(), 1.0);
+    }
+
+    #[test]
+    fn pause_skips_the_time_spent_paused() {
+        let mut clock = Clock::new(FRAME_MS);
+        clock.tick(Some(0.0));
+        clock.tick(Some(100.0));
+        clock.pause();
+        assert_eq!(clock.tick(Some(5000.0)), 100.0);
+        clock.resume();
+        assert_eq!(clock.tick(Some(6000.0)), 100.0);
+        assert_eq!(clock.tick(Some(6050.0)), 150.0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Errors raised while setting up or drawing an art. They reach JS as `Error`
+//! objects carrying a `kind` plus the fields of the variant, so callers can
+//! tell a missing canvas from a shader that failed to compile.
+
+use js_sys::Reflect;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use web_sys::WebGl2RenderingContext as GL;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn from_gl(shader_type: u32) -> Self {
+        if shader_type == GL::VERTEX_SHADER {
+            ShaderStage::Vertex
+        } else {
+            ShaderStage::Fragment
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// Neither `window` nor `document` is available, e.g. in a worker.
+    NoWindow,
+    /// No canvas element matches the selector.
+    MissingCanvas {
+        selector: String,
+    },
+    /// The canvas has no WebGL2 context to give.
+    NoWebGl2,
+    ShaderCompile {
+        stage: ShaderStage,
+        log: String,
+    },
+    ShaderLink {
+        log: String,
+    },
+    MissingUniform {
+        name: String,
+    },
+    MissingAttribute {
+        name: String,
+    },
+    /// A `gl.create*` call returned null, usually because the context is lost.
+    CreateObject {
+        object: &'static str,
+    },
+    UnknownArt {
+        id: String,
+    },
+    /// A worker message is missing a field or has one of the wrong type.
+    InvalidMessage {
+        reason: String,
+    },
+    /// SVG path data that can't be parsed or encloses no area.
+    InvalidPath {
+        reason: String,
+    },
+    /// Heart rate data that can't be read or has a sample out of range.
+    InvalidRhythm {
+        reason: String,
+    },
+    /// A Heartbeat setting out of range.
+    InvalidConfig {
+        field: &'static str,
+        reason: String,
+    },
+    /// Exception thrown by a browser API.
+    Js(JsValue),
+}
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NoWindow => "NoWindow",
+            Error::MissingCanvas { .. } => "MissingCanvas",
+            Error::NoWebGl2 => "NoWebGl2",
+            Error::ShaderCompile { .. } => "ShaderCompile",
+            Error::ShaderLink { .. } => "ShaderLink",
+            Error::MissingUniform { .. } => "MissingUniform",
+            Error::MissingAttribute { .. } => "MissingAttribute",
+            Error::CreateObject { .. } => "CreateObject",
+            Error::UnknownArt { .. } => "UnknownArt",
+            Error::InvalidMessage { .. } => "InvalidMessage",
+            Error::InvalidPath { .. } => "InvalidPath",
+            Error::InvalidRhythm { .. } => "InvalidRhythm",
+            Error::InvalidConfig { .. } => "InvalidConfig",
+            Error::Js(_) => "Js",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoWindow => write!(f, "No window or document found"),
+            Error::MissingCanvas { selector } => write!(f, "Canvas not found: {}", selector),
+            Error::NoWebGl2 => write!(f, "WebGL2 context creation failed"),
+            Error::ShaderCompile { stage, log } => {
+                write!(f, "{} shader compilation failed: {}", stage, log)
+            }
+            Error::ShaderLink { log } => write!(f, "Program linking failed: {}", log),
+            Error::MissingUniform { name } => write!(f, "{} uniform not found", name),
+            Error::MissingAttribute { name } => write!(f, "{} attribute not found", name),
+            Error::CreateObject { object } => write!(f, "Failed to create {}", object),
+            Error::UnknownArt { id } => write!(f, "Unknown art: {}", id),
+            Error::InvalidMessage { reason } => write!(f, "Invalid worker message: {}", reason),
+            Error::InvalidPath { reason } => write!(f, "Invalid SVG path: {}", reason),
+            Error::InvalidRhythm { reason } => write!(f, "Invalid heart rate data: {}", reason),
+            Error::InvalidConfig { field, reason } => {
+                write!(f, "Invalid Heartbeat config: {} {}", field, reason)
+            }
+            Error::Js(value) => match value.as_string() {
+                Some(message) => write!(f, "{}", message),
+                None => write!(f, "{:?}", value),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Self {
+        Error::Js(value)
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(error: Error) -> Self {
+        // Browser exceptions are passed through untouched
+        if let Error::Js(value) = error {
+            return value;
+        }
+
+        let object = js_sys::Error::new(&error.to_string());
+        object.set_name("GaglError");
+        let set = |key: &str, value: JsValue| {
+            let _ = Reflect::set(&object, &JsValue::from_str(key), &value);
+        };
+        set("kind", error.kind().into());
+        match &error {
+            Error::MissingCanvas { selector } => set("selector", selector.into()),
+            Error::ShaderCompile { stage, log } => {
+                set("stage", stage.to_string().into());
+                set("log", log.into());
+            }
+            Error::ShaderLink { log } => set("log", log.into()),
+            Error::MissingUniform { name } | Error::MissingAttribute { name } => {
+                set("name", name.into())
+            }
+            Error::CreateObject { object } => set("object", (*object).into()),
+            Error::UnknownArt { id } => set("id", id.into()),
+            Error::InvalidMessage { reason }
+            | Error::InvalidPath { reason }
+            | Error::InvalidRhythm { reason } => set("reason", reason.into()),
+            Error::InvalidConfig { field, reason } => {
+                set("field", (*field).into());
+                set("reason", reason.into());
+            }
+            Error::NoWindow | Error::NoWebGl2 | Error::Js(_) => {}
+        }
+        object.into()
+    }
+}
@@ -1,19 +1,28 @@
 //! Heart animation using Rust, WebAssembly and WebGL
 //! lib.rs
 
+use crate::art::Art;
+use crate::clock::{Clock, FRAME_MS};
+use crate::error::Error;
+use crate::heartbeat_gpu::{GpuParticles, ParticleInit};
 use crate::helpers::*;
-use js_sys::Float32Array;
+use crate::layout::Layout;
+use crate::poisson::poisson_disk;
+use crate::program::Program;
+use crate::render_loop::RenderLoop;
+use crate::rhythm::{Rhythm, RhythmUnit};
+use crate::shape::Shape;
+use crate::surface::{ContextWatch, Surface, Viewport};
+use js_sys::{Float32Array, Function};
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use std::cell::RefCell;
 use std::f32::consts::PI;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
-use web_sys::{window, WebGl2RenderingContext as GL, WebGlBuffer, WebGlProgram};
-use web_sys::{HtmlCanvasElement, Performance};
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext as GL};
 
-// Animation phases
+// Animation phases, in the order of `uPhase` in the GPU update shader
 #[derive(PartialEq, Copy, Clone)]
 enum Phase {
     Initializing,
@@ -22,6 +31,161 @@ enum Phase {
     Reforming,
 }
 
+/// Tunables of the Heartbeat animation, settable from JS.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HeartbeatConfig {
+    /// Beats per minute.
+    pub heart_rate: f32,
+    /// Range of the outward push an outline particle gets on a beat, in
+    /// units per step.
+    pub outline_force_min: f32,
+    pub outline_force_max: f32,
+    /// Same for the fill particles.
+    pub fill_force_min: f32,
+    pub fill_force_max: f32,
+    /// Downward pull on exploding particles, per step.
+    pub gravity: f32,
+    /// Fraction of its velocity an exploding particle keeps each step.
+    pub damping: f32,
+    /// Minimum distance between fill particles, at least `MIN_SPACING`.
+    pub spacing: f32,
+    /// Point size in design pixels.
+    pub size: f32,
+    /// Particles along the outline, at most `MAX_OUTLINE_POINTS`.
+    pub outline_points: u32,
+    /// Reach of the pointer in design pixels.
+    pub pointer_radius: f32,
+    /// Distance a particle right under the pointer is pushed or pulled per
+    /// step, fading out towards `pointer_radius`.
+    pub pointer_strength: f32,
+    /// Seed of the particle layout and explosion forces. Unset, a new heart
+    /// picks a random one and `set_config` keeps the current one.
+    pub seed: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl HeartbeatConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        HeartbeatConfig {
+            heart_rate: 60.0,
+            outline_force_min: 2.0,
+            outline_force_max: 4.0,
+            fill_force_min: 1.0,
+            fill_force_max: 3.0,
+            gravity: 0.05,
+            damping: 0.95,
+            spacing: 12.0,
+            size: 4.0,
+            outline_points: 63,
+            pointer_radius: 80.0,
+            pointer_strength: 6.0,
+            seed: None,
+        }
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeartbeatConfig {
+    /// Checks every field is in range, e.g. that the heart rate is positive
+    /// so the heart beats at all.
+    fn validate(&self) -> Result<(), Error> {
+        let check = |field, ok: bool, reason: &str| {
+            if ok {
+                Ok(())
+            } else {
+                Err(Error::InvalidConfig {
+                    field,
+                    reason: reason.to_string(),
+                })
+            }
+        };
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        let range =
+            |min: f32, max: f32| min.is_finite() && max.is_finite() && 0.0 <= min && min <= max;
+
+        check("heart_rate", positive(self.heart_rate), "must be positive")?;
+        check(
+            "outline_force_min",
+            range(self.outline_force_min, self.outline_force_max),
+            "must be at least 0 and at most outline_force_max",
+        )?;
+        check(
+            "fill_force_min",
+            range(self.fill_force_min, self.fill_force_max),
+            "must be at least 0 and at most fill_force_max",
+        )?;
+        check("gravity", self.gravity.is_finite(), "must be a number")?;
+        check(
+            "damping",
+            (0.0..=1.0).contains(&self.damping),
+            "must be between 0 and 1",
+        )?;
+        check(
+            "spacing",
+            self.spacing.is_finite() && self.spacing >= MIN_SPACING,
+            &format!("must be at least {}", MIN_SPACING),
+        )?;
+        check("size", positive(self.size), "must be positive")?;
+        check(
+            "outline_points",
+            (1..=MAX_OUTLINE_POINTS).contains(&self.outline_points),
+            &format!("must be between 1 and {}", MAX_OUTLINE_POINTS),
+        )?;
+        check(
+            "pointer_radius",
+            positive(self.pointer_radius),
+            "must be positive",
+        )?;
+        check(
+            "pointer_strength",
+            self.pointer_strength.is_finite() && self.pointer_strength >= 0.0,
+            "must be at least 0",
+        )
+    }
+
+    /// Whether going from `self` to `other` changes where the particles are,
+    /// rather than only how they move.
+    fn reshapes(&self, other: &HeartbeatConfig) -> bool {
+        self.spacing != other.spacing
+            || self.size != other.size
+            || self.outline_points != other.outline_points
+    }
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Initializing => "Initializing",
+            Phase::Formed => "Formed",
+            Phase::Exploding => "Exploding",
+            Phase::Reforming => "Reforming",
+        }
+    }
+}
+
+/// Something JS may be listening for, raised while stepping.
+enum HeartEvent {
+    Beat { beat: u32, time: f64 },
+    Phase(Phase),
+}
+
+/// What the colour modes measure the particles against.
+struct ColorScale {
+    /// Middle of the current shape's bounds.
+    center: [f32; 2],
+    /// Half the larger side of the current shape's bounds.
+    radius: f32,
+    /// Fastest start of an explosion, in units per step.
+    max_speed: f32,
+}
+
 // Particle structure
 struct Particle {
     x: f32,
@@ -57,18 +221,22 @@ impl Particle {
         }
     }
 
-    fn explode(&mut self, rng: &mut SmallRng) {
-        let angle = self.y.atan2(self.x);
-        let force = if self.is_outline {
-            rng.gen_range(2.0..4.0)
+    /// Sends the particle flying away from `center`.
+    fn explode(&mut self, [cx, cy]: [f32; 2], rng: &mut SmallRng, config: &HeartbeatConfig) {
+        let angle = (self.y - cy).atan2(self.x - cx);
+        // Not `gen_range`, which panics on the empty range of min == max
+        let (min, max) = if self.is_outline {
+            (config.outline_force_min, config.outline_force_max)
         } else {
-            rng.gen_range(1.0..3.0)
+            (config.fill_force_min, config.fill_force_max)
         };
+        let force = min + (max - min) * rng.gen::<f32>();
         self.vx = angle.cos() * force;
         self.vy = angle.sin() * force;
     }
 
-    fn update(&mut self, phase: Phase) {
+    /// Advances the particle by one fixed physics step.
+    fn update(&mut self, phase: Phase, config: &HeartbeatConfig) {
         match phase {
             Phase::Initializing => {
                 let dx = self.target_x - self.x;
@@ -79,9 +247,9 @@ impl Particle {
             Phase::Exploding => {
                 self.x += self.vx;
                 self.y += self.vy;
-                self.vy += 0.05;
-                self.vx *= 0.95;
-                self.vy *= 0.95;
+                self.vy += config.gravity;
+                self.vx *= config.damping;
+                self.vy *= config.damping;
                 self.alpha = f32::max(self.alpha - 1.0, 100.0);
             }
             Phase::Reforming => {
@@ -91,7 +259,53 @@ impl Particle {
                 self.y += dy * self.reform_speed;
                 self.alpha = f32::min(self.alpha + 5.0, 255.0);
             }
-            Phase::Formed => {}
+            Phase::Formed => {
+                // Springs back after the pointer pushed it out of place
+                self.x += (self.target_x - self.x) * self.reform_speed;
+                self.y += (self.target_y - self.y) * self.reform_speed;
+            }
+        }
+    }
+
+    /// Pushes the particle away from `pointer` by up to `strength`, or pulls
+    /// it in for a negative one, if it's within `radius`.
+    fn push_from(&mut self, [px, py]: [f32; 2], strength: f32, radius: f32) {
+        let (dx, dy) = (self.x - px, self.y - py);
+        let distance = dx.hypot(dy);
+        if distance >= radius || distance == 0.0 {
+            return;
+        }
+        // A pull stops at the pointer rather than overshoot it
+        let push = (strength * (1.0 - distance / radius)).max(-distance);
+        self.x += dx / distance * push;
+        self.y += dy / distance * push;
+    }
+
+    /// Distance the particle moves in the next step of `phase`.
+    fn speed(&self, phase: Phase) -> f32 {
+        let to_target = (self.target_x - self.x).hypot(self.target_y - self.y);
+        match phase {
+            Phase::Initializing => to_target * self.initial_speed,
+            Phase::Exploding => self.vx.hypot(self.vy),
+            Phase::Reforming | Phase::Formed => to_target * self.reform_speed,
+        }
+    }
+
+    /// Colour of the particle in `mode`, scaling the art's colour.
+    fn color(&self, mode: ColorMode, phase: Phase, scale: &ColorScale) -> [f32; 4] {
+        let shade = |level: f32| [level, level, level, 1.0];
+        match mode {
+            ColorMode::Fade => [1.0, 1.0, 1.0, self.alpha / 255.0],
+            ColorMode::Gradient if self.is_outline => shade(1.0),
+            ColorMode::Gradient => {
+                // Darker towards the middle of the shape
+                let [cx, cy] = scale.center;
+                let distance = (self.target_x - cx).hypot(self.target_y - cy);
+                shade(0.35 + 0.65 * (distance / scale.radius).min(1.0))
+            }
+            ColorMode::Velocity => {
+                shade(0.3 + 0.7 * (self.speed(phase) / scale.max_speed).min(1.0))
+            }
         }
     }
 
@@ -102,142 +316,482 @@ impl Particle {
     }
 }
 
+/// Length of a physics step; the per-step constants are tuned for 60 Hz.
+const STEP_MS: f64 = FRAME_MS;
+
+/// Most steps a frame catches up on. Time beyond, e.g. a tab coming back
+/// from the background, is dropped rather than stalling the frame on it.
+const MAX_CATCH_UP_STEPS: u64 = 60;
+
+// The GPU simulation can't count the particles near their target without
+// reading the buffers back, so it changes phase after a fixed number of
+// steps instead. The slowest particle closes 2% of the gap per step while
+// initializing, and takes ln(2 / 600) / ln(0.98) ≈ 282 steps to get within
+// 2 units from 600 away. Reforming at 5% per step from up to 256 units out,
+// across a whole shape when morphing, takes ln(2 / 256) / ln(0.95) ≈ 95.
+const GPU_INITIALIZE_STEPS: u64 = 282;
+const GPU_REFORM_STEPS: u64 = 95;
+
+/// How the Heartbeat particles are shaded, on top of the art's colour.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorMode {
+    /// Fades the particles out as they fly apart and back in as they reform.
+    Fade,
+    /// Bright outline, fill darkening towards the middle.
+    Gradient,
+    /// Brighter the faster a particle moves.
+    Velocity,
+}
+
+/// What the pointer does to the Heartbeat particles near it.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PointerMode {
+    Off,
+    Repel,
+    Attract,
+}
+
+impl PointerMode {
+    /// Push away from the pointer per step, negative to pull towards it.
+    fn strength(self, config: &HeartbeatConfig) -> f32 {
+        match self {
+            PointerMode::Off => 0.0,
+            PointerMode::Repel => config.pointer_strength,
+            PointerMode::Attract => -config.pointer_strength,
+        }
+    }
+}
+
+/// Where the Heartbeat particle physics runs.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Simulation {
+    /// One `Particle` at a time in Rust, the reference implementation.
+    Cpu,
+    /// All particles at once in a transform feedback pass, for counts in the
+    /// hundreds of thousands.
+    Gpu,
+}
+
+/// Smallest fill spacing, which bounds the number of fill particles.
+const MIN_SPACING: f32 = 1.0;
+
+/// Most particles in the GPU simulation, which keeps its buffers to tens of
+/// megabytes.
+const MAX_GPU_PARTICLES: u32 = 1_000_000;
+
+/// Most outline particles, far more than an outline can show apart.
+const MAX_OUTLINE_POINTS: u32 = 4096;
+
+/// Vertex layout of a particle: x, y, r, g, b, a, size.
+const FLOATS_PER_VERTEX: usize = 7;
+const VERTEX_STRIDE: i32 = (FLOATS_PER_VERTEX * 4) as i32;
+
 struct HeartAnimation {
     particles: Vec<Particle>,
     phase: Phase,
-    heart_rate: f32,
+    config: HeartbeatConfig,
+    /// Seed in use, whatever `config.seed` says.
+    seed: u32,
     last_beat: f64,
     gl: GL,
-    program: WebGlProgram,
-    vertex_buffer: WebGlBuffer,
-    start_time: f64,
+    resources: Option<GpuResources>,
     rng: SmallRng,
-    canvas_width: f32,
-    canvas_height: f32,
+    viewport: Viewport,
+    /// Number of fixed physics steps simulated so far.
+    steps: u64,
+    /// Vertex data of the last frame, kept to reuse its allocation.
+    vertex_data: Vec<f32>,
+    /// Size of the GPU vertex buffer in floats.
+    buffer_capacity: usize,
+    /// Particle state on the GPU, replacing `particles` when set.
+    gpu: Option<GpuParticles>,
+    /// Step at which the current phase began.
+    phase_start: u64,
+    /// Number of beats so far, also seeds the GPU explosion forces.
+    beats: u32,
+    /// Beats and phase changes since the controller last looked.
+    events: Vec<HeartEvent>,
+    /// Pointer position in design pixels, while it's over the canvas.
+    pointer: Option<[f32; 2]>,
+    pointer_mode: PointerMode,
+    /// Centre of an explosion asked for by a click, set off on the next step.
+    click: Option<[f32; 2]>,
+    /// Recorded beats to follow instead of `config.heart_rate`.
+    rhythm: Option<Rhythm>,
+    /// Next beat of `rhythm` to play.
+    rhythm_index: usize,
+    /// Shapes the particles form in turn, one per beat.
+    shapes: Vec<Shape>,
+    shape_index: usize,
+    /// Target of every particle in each shape, the outline ones first.
+    targets: Vec<Vec<[f32; 2]>>,
+    outline_count: usize,
+    /// Random state right after `targets` were sampled, `None` when they
+    /// have to be sampled again. Resets pick up from here rather than run
+    /// the sampling once more.
+    sampled_rng: Option<SmallRng>,
+    /// Same for the GPU particles, which outnumber the CPU ones.
+    gpu_targets: Vec<Vec<[f32; 2]>>,
+    color_mode: ColorMode,
 }
 
 impl HeartAnimation {
-    fn new(gl: GL, width: f32, height: f32) -> Result<Self, JsValue> {
-        let program = setup_shaders(&gl)?;
-        let vertex_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
-
-        gl.use_program(Some(&program));
-        match gl.get_uniform_location(&program, "color_multiplier") {
-            Some(color_location) => {
-                gl.uniform3f(Some(&color_location), 1.0, 0.0, 0.29);
-            }
-            None => {
-                console_error!("color_multiplier uniform not found");
-            }
-        };
-
-        let performance = window().unwrap().performance().unwrap();
-        let start_time = performance.now();
+    fn new(gl: GL, viewport: Viewport, config: HeartbeatConfig) -> Result<Self, Error> {
+        let resources = create_resources(&gl)?;
+        Ok(HeartAnimation::with_resources(
+            gl,
+            Some(resources),
+            viewport,
+            config,
+        ))
+    }
 
-        let rng = SmallRng::seed_from_u64(42);
+    /// Lays out the particles to render with `resources`. The CPU
+    /// simulation runs without them, it only needs them to render.
+    fn with_resources(
+        gl: GL,
+        resources: Option<GpuResources>,
+        viewport: Viewport,
+        config: HeartbeatConfig,
+    ) -> Self {
+        let seed = config.seed.unwrap_or_else(random_seed);
+        let rng = SmallRng::seed_from_u64(seed as u64);
         let mut heart = HeartAnimation {
             particles: Vec::new(),
             phase: Phase::Initializing,
-            heart_rate: 60.0,
+            config,
+            seed,
             last_beat: 0.0,
             gl,
-            program,
-            vertex_buffer,
-            start_time,
+            resources,
             rng,
-            canvas_width: width,
-            canvas_height: height,
+            viewport,
+            steps: 0,
+            vertex_data: Vec::new(),
+            buffer_capacity: 0,
+            gpu: None,
+            phase_start: 0,
+            beats: 0,
+            events: Vec::new(),
+            pointer: None,
+            pointer_mode: PointerMode::Repel,
+            click: None,
+            rhythm: None,
+            rhythm_index: 0,
+            shapes: vec![Shape::heart()],
+            shape_index: 0,
+            targets: Vec::new(),
+            outline_count: 0,
+            sampled_rng: None,
+            gpu_targets: Vec::new(),
+            color_mode: ColorMode::Fade,
         };
 
         heart.initialize_particles();
-        Ok(heart)
+        heart
     }
 
-    fn get_heart_x(t: f32) -> f32 {
-        16.0 * (t.sin().powi(3))
+    fn initialize_particles(&mut self) {
+        let size = self.config.size;
+        match &self.sampled_rng {
+            Some(rng) => self.rng = rng.clone(),
+            None => {
+                self.sample_shapes();
+                self.sampled_rng = Some(self.rng.clone());
+            }
+        }
+        self.shape_index = 0;
+
+        for (i, &[x, y]) in self.targets[0].iter().enumerate() {
+            let is_outline = i < self.outline_count;
+            self.particles
+                .push(Particle::new(x, y, is_outline, size, &mut self.rng));
+        }
     }
 
-    fn get_heart_y(t: f32) -> f32 {
-        13.0 * t.cos() - 5.0 * (2.0 * t).cos() - 2.0 * (3.0 * t).cos() - (4.0 * t).cos()
+    /// Lays out every shape of the sequence, then matches their point counts
+    /// so each particle has a target in each shape.
+    fn sample_shapes(&mut self) {
+        let (outlines, fills): (Vec<_>, Vec<_>) = (0..self.shapes.len())
+            .map(|index| self.sample_targets(index))
+            .unzip();
+        self.outline_count = outlines.iter().map(Vec::len).max().unwrap_or(0);
+        let fill_count = fills.iter().map(Vec::len).max().unwrap_or(0);
+        self.targets = match_counts(outlines, self.outline_count)
+            .into_iter()
+            .zip(match_counts(fills, fill_count))
+            .map(|(outline, fill)| [outline, fill].concat())
+            .collect();
     }
 
-    fn is_inside_heart(x: f32, y: f32) -> bool {
-        let px = x / 8.0;
-        let py = -y / 8.0;
-        let px2 = px * px;
-        let py2 = py * py;
-        py2 < (4.0 - px2) * (1.0 - px2)
+    /// Has the targets sampled again on the next reset, after the seed,
+    /// layout settings or shapes changed.
+    fn resample(&mut self) {
+        self.sampled_rng = None;
     }
 
-    fn initialize_particles(&mut self) {
-        // Create outline particles
-        for i in 0..63 {
-            let t = (i as f32) * 2.0 * PI / 63.0;
-            let x = Self::get_heart_x(t) * 8.0;
-            let y = -Self::get_heart_y(t) * 8.0;
-            self.particles
-                .push(Particle::new(x, y, true, 4.0, &mut self.rng));
-        }
+    /// Outline and fill points of shape `index`, the fill evenly spread
+    /// at the configured spacing from each other and the outline.
+    fn sample_targets(&mut self, index: usize) -> (Vec<[f32; 2]>, Vec<[f32; 2]>) {
+        let shape = &self.shapes[index];
+        let outline = shape.outline(self.config.outline_points as usize);
+        let fill = poisson_disk(
+            shape.bounds(),
+            self.config.spacing,
+            &outline,
+            |x, y| shape.contains(x, y),
+            &mut self.rng,
+        );
+        (outline, fill)
+    }
 
-        // Create fill particles
-        let spacing = 12.0;
+    /// Up to `count` random points inside shape `index`, fewer if the shape
+    /// is too thin to hit.
+    fn random_inside(&mut self, index: usize, count: usize) -> Vec<[f32; 2]> {
+        let shape = &self.shapes[index];
+        let mut points = Vec::with_capacity(count);
         let mut attempts = 0;
-        let max_attempts = 1000;
+        while points.len() < count && attempts < count * 100 {
+            attempts += 1;
+            let [x, y] = shape.random_point(&mut self.rng);
+            if shape.contains(x, y) {
+                points.push([x, y]);
+            }
+        }
+        points
+    }
 
-        while attempts < max_attempts {
-            let t = self.rng.gen::<f32>() * 2.0 * PI;
-            let scale = self.rng.gen_range(0.1..0.9);
+    /// Re-targets the particles at the next shape of the sequence, which
+    /// they reform into after the explosion.
+    fn next_shape(&mut self) {
+        if self.shapes.len() < 2 {
+            return;
+        }
+        self.shape_index = (self.shape_index + 1) % self.shapes.len();
+        for (p, &[x, y]) in self
+            .particles
+            .iter_mut()
+            .zip(&self.targets[self.shape_index])
+        {
+            p.target_x = x;
+            p.target_y = y;
+        }
+        if let Some(gpu) = &mut self.gpu {
+            gpu.retarget(&self.gpu_targets[self.shape_index]);
+        }
+    }
 
-            let x = Self::get_heart_x(t) * 8.0 * scale;
-            let y = -Self::get_heart_y(t) * 8.0 * scale;
+    /// Rebuilds the particles from the seed, as they were at time 0.
+    fn reset(&mut self) {
+        self.rng = SmallRng::seed_from_u64(self.seed as u64);
+        self.particles.clear();
+        self.phase = Phase::Initializing;
+        self.last_beat = 0.0;
+        self.steps = 0;
+        self.phase_start = 0;
+        self.beats = 0;
+        self.events.clear();
+        self.rhythm_index = 0;
+        self.initialize_particles();
+        if let Some(gpu) = &mut self.gpu {
+            gpu.reset();
+            if self.shapes.len() > 1 {
+                gpu.retarget(&self.gpu_targets[0]);
+            }
+        }
+    }
 
-            if Self::is_inside_heart(x, y) {
-                let mut too_close = false;
-                for p in &self.particles {
-                    let dx = x - p.target_x;
-                    let dy = y - p.target_y;
-                    let dist = (dx * dx + dy * dy).sqrt();
-                    if dist < spacing {
-                        too_close = true;
-                        break;
-                    }
-                }
+    /// Moves the simulation onto the GPU with `count` particles, or back to
+    /// the CPU with `None`, starting over from time 0.
+    fn use_gpu(&mut self, count: Option<usize>) -> Result<(), Error> {
+        if let Some(gpu) = self.gpu.take() {
+            gpu.delete();
+        }
+        self.reset();
+        let Some(count) = count else {
+            return Ok(());
+        };
+        let particles = self.gpu_particles(count);
+        let Some(resources) = &self.resources else {
+            return Ok(());
+        };
+        self.gpu = Some(GpuParticles::new(&self.gl, &resources.program, &particles)?);
+        Ok(())
+    }
 
-                if !too_close {
-                    self.particles
-                        .push(Particle::new(x, y, false, 4.0, &mut self.rng));
-                }
-            }
-            attempts += 1;
+    /// The first `count` CPU particles, topped up to `count` with fill
+    /// particles that skip the spacing check, sized down so the shape keeps
+    /// its density. Also fills `gpu_targets` to match.
+    fn gpu_particles(&mut self, count: usize) -> Vec<ParticleInit> {
+        let kept = count.min(self.particles.len());
+        let extra = count - kept;
+        let extra_targets: Vec<_> = (0..self.shapes.len())
+            .map(|index| self.random_inside(index, extra))
+            .collect();
+        let extra_targets = match_counts(extra_targets, extra);
+        self.gpu_targets = self
+            .targets
+            .iter()
+            .zip(&extra_targets)
+            .map(|(targets, extra)| [&targets[..kept], extra].concat())
+            .collect();
+
+        let mut particles: Vec<ParticleInit> = self.particles[..kept]
+            .iter()
+            .map(|p| ParticleInit {
+                x: p.x,
+                y: p.y,
+                target_x: p.target_x,
+                target_y: p.target_y,
+                is_outline: p.is_outline,
+                size: p.size,
+                reform_speed: p.reform_speed,
+                initial_speed: p.initial_speed,
+                random: self.rng.gen(),
+            })
+            .collect();
+
+        let full_size = self.config.size;
+        let size =
+            (full_size * (1000.0 / count as f32).sqrt()).clamp(full_size.min(1.0), full_size);
+        for &[target_x, target_y] in &extra_targets[0] {
+            let p = Particle::new(target_x, target_y, false, size, &mut self.rng);
+            particles.push(ParticleInit {
+                x: p.x,
+                y: p.y,
+                target_x,
+                target_y,
+                is_outline: false,
+                size,
+                reform_speed: p.reform_speed,
+                initial_speed: p.initial_speed,
+                random: self.rng.gen(),
+            });
         }
+        particles
+    }
 
-        // Add extra points for the bottom region
-        for _ in 0..5 {
-            let x = self.rng.gen_range(-10.0..10.0);
-            let y = self.rng.gen_range(80.0..100.0);
+    fn sim_time(&self) -> f64 {
+        self.steps as f64 * STEP_MS
+    }
 
-            if Self::is_inside_heart(x, y) {
-                let mut too_close = false;
-                for p in &self.particles {
-                    let dx = x - p.target_x;
-                    let dy = y - p.target_y;
-                    let dist = (dx * dx + dy * dy).sqrt();
-                    if dist < spacing * 0.8 {
-                        too_close = true;
-                        break;
-                    }
-                }
+    /// Runs as many fixed steps as fit in `time` milliseconds, so the same
+    /// time always gives the same particles whatever the refresh rate. Going
+    /// back in time starts over, see `rewind`.
+    fn advance(&mut self, time: f64) -> Result<(), Error> {
+        if time < self.sim_time() {
+            self.rewind(time);
+        }
+        while self.sim_time() + STEP_MS <= time {
+            self.steps += 1;
+            self.step(self.sim_time())?;
+        }
+        Ok(())
+    }
 
-                if !too_close {
-                    self.particles
-                        .push(Particle::new(x, y, false, 4.0, &mut self.rng));
-                }
-            }
+    /// Resets the simulation for a replay up to `time`. The GPU simulation
+    /// isn't replayed, which would take a transform feedback pass for every
+    /// step since 0, but starts over from the scattered particles at `time`.
+    fn rewind(&mut self, time: f64) {
+        self.reset();
+        if self.gpu.is_none() {
+            return;
+        }
+        let mut steps = (time.max(0.0) / STEP_MS) as u64;
+        if steps as f64 * STEP_MS > time {
+            steps = steps.saturating_sub(1);
+        }
+        self.steps = steps;
+        self.phase_start = steps;
+        // The next beat is a whole beat away, as it is from time 0
+        self.last_beat = self.sim_time();
+        if let Some(rhythm) = &self.rhythm {
+            self.rhythm_index = rhythm.next_after(self.sim_time(), 0);
         }
     }
 
-    fn update(&mut self, timestamp: f64) {
-        let frame_count = ((timestamp - self.start_time) / 16.67) as u32; // ~60fps
+    /// Replays the simulation up to `time` milliseconds, see `rewind`.
+    fn seek(&mut self, time: f64) -> Result<(), Error> {
+        let phase = self.phase;
+        self.rewind(time);
+        self.advance(time)?;
+        // The replayed beats are history, not news, but where they left the
+        // particles is
+        self.events.clear();
+        if self.phase != phase {
+            self.events.push(HeartEvent::Phase(self.phase));
+        }
+        Ok(())
+    }
+
+    /// Whether the next beat is due at `time`, from the recording once it
+    /// started playing and from the heart rate before.
+    fn beat_due(&self, time: f64) -> bool {
+        if let Some(rhythm) = self.rhythm.as_ref().filter(|rhythm| time >= rhythm.began) {
+            return rhythm
+                .beat_time(self.rhythm_index)
+                .is_some_and(|beat| time >= beat);
+        }
+        let beat_ms = 60_000.0 / self.config.heart_rate as f64;
+        time - self.last_beat > beat_ms
+    }
+
+    fn set_phase(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.phase_start = self.steps;
+        self.events.push(HeartEvent::Phase(phase));
+    }
+
+    /// Counts a beat at `time`, once the particles have been sent flying.
+    fn beat(&mut self, time: f64) {
+        self.beats += 1;
+        self.last_beat = time;
+        if let Some(rhythm) = &self.rhythm {
+            // Beats missed while the particles were still reforming are
+            // dropped rather than played back to back
+            self.rhythm_index = rhythm.next_after(time, self.rhythm_index);
+        }
+        self.next_shape();
+        self.events.push(HeartEvent::Beat {
+            beat: self.beats,
+            time,
+        });
+    }
+
+    /// Sets off the explosion of a pending click, returning its centre.
+    fn take_click(&mut self, time: f64) -> Option<[f32; 2]> {
+        let center = self.click.take()?;
+        if self.phase == Phase::Exploding {
+            // Starts the explosion over without announcing it again
+            self.phase_start = self.steps;
+        } else {
+            self.set_phase(Phase::Exploding);
+        }
+        self.last_beat = time;
+        Some(center)
+    }
+
+    /// Pointer position and the signed strength it acts with, if any.
+    fn pointer_force(&self) -> Option<([f32; 2], f32)> {
+        let strength = self.pointer_mode.strength(&self.config);
+        self.pointer
+            .filter(|_| strength != 0.0)
+            .map(|pointer| (pointer, strength))
+    }
+
+    fn step(&mut self, time: f64) -> Result<(), Error> {
+        if self.gpu.is_some() {
+            return self.step_gpu(time);
+        }
+
+        if let Some(center) = self.take_click(time) {
+            for p in &mut self.particles {
+                p.explode(center, &mut self.rng, &self.config);
+            }
+        }
 
         match self.phase {
             Phase::Initializing => {
@@ -249,22 +803,21 @@ impl HeartAnimation {
                 }
 
                 if particles_in_position as f32 > self.particles.len() as f32 * 0.95 {
-                    self.phase = Phase::Formed;
+                    self.set_phase(Phase::Formed);
                 }
             }
             Phase::Formed => {
-                let beat_frames = (60.0 * 60.0 / self.heart_rate) as f64;
-                if timestamp - self.last_beat > beat_frames {
-                    self.phase = Phase::Exploding;
+                if self.beat_due(time) {
+                    self.set_phase(Phase::Exploding);
                     for p in &mut self.particles {
-                        p.explode(&mut self.rng);
+                        p.explode([0.0, 0.0], &mut self.rng, &self.config);
                     }
-                    self.last_beat = timestamp;
+                    self.beat(time);
                 }
             }
             Phase::Exploding => {
-                if timestamp - self.last_beat > 20.0 * 16.67 {
-                    self.phase = Phase::Reforming;
+                if time - self.last_beat > 20.0 * STEP_MS {
+                    self.set_phase(Phase::Reforming);
                 }
             }
             Phase::Reforming => {
@@ -276,18 +829,70 @@ impl HeartAnimation {
                 }
 
                 if particles_reformed as f32 > self.particles.len() as f32 * 0.95 {
-                    self.phase = Phase::Formed;
+                    self.set_phase(Phase::Formed);
                 }
             }
         }
 
         // Update particles
         for p in &mut self.particles {
-            p.update(self.phase);
+            p.update(self.phase, &self.config);
+        }
+        if let Some((pointer, strength)) = self.pointer_force() {
+            for p in &mut self.particles {
+                p.push_from(pointer, strength, self.config.pointer_radius);
+            }
+        }
+        Ok(())
+    }
+
+    /// `step` for the GPU simulation, with the phase changes timed by
+    /// `GPU_INITIALIZE_STEPS` and `GPU_REFORM_STEPS`.
+    fn step_gpu(&mut self, time: f64) -> Result<(), Error> {
+        let mut explode = self.take_click(time);
+        let in_phase = self.steps - self.phase_start;
+        match self.phase {
+            Phase::Initializing => {
+                if in_phase > GPU_INITIALIZE_STEPS {
+                    self.set_phase(Phase::Formed);
+                }
+            }
+            Phase::Formed => {
+                if self.beat_due(time) {
+                    self.set_phase(Phase::Exploding);
+                    explode = Some([0.0, 0.0]);
+                    self.beat(time);
+                }
+            }
+            Phase::Exploding => {
+                if time - self.last_beat > 20.0 * STEP_MS {
+                    self.set_phase(Phase::Reforming);
+                }
+            }
+            Phase::Reforming => {
+                if in_phase > GPU_REFORM_STEPS {
+                    self.set_phase(Phase::Formed);
+                }
+            }
         }
+
+        let pointer = self.pointer_force();
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+        gpu.step(
+            self.phase as i32,
+            explode,
+            self.beats,
+            &self.config,
+            pointer,
+        )
     }
 
-    fn render(&self) {
+    fn render(&mut self, layout: &Layout) -> Result<(), Error> {
+        let Some(resources) = &self.resources else {
+            return Ok(());
+        };
         let gl = &self.gl;
 
         // Clear canvas
@@ -295,110 +900,157 @@ impl HeartAnimation {
         gl.clear(GL::COLOR_BUFFER_BIT);
 
         // Use shader program
-        gl.use_program(Some(&self.program));
-
-        // Set viewport transform - flip y to match canvas coordinate system
-        let u_transform = gl
-            .get_uniform_location(&self.program, "uTransform")
-            .unwrap();
-        gl.uniform_matrix4fv_with_f32_array(
-            Some(&u_transform),
-            false,
+        let program = &resources.program;
+        program.use_program();
+
+        // Set model transform - particles are around the origin with y down,
+        // flip y to match canvas coordinate system
+        let [sx, sy, ox, oy] = layout.transform(&self.viewport);
+        program.set_mat4(
+            "uTransform",
             &[
-                2.0 / self.canvas_width,
-                0.0,
-                0.0,
-                0.0,
-                0.0,
-                -2.0 / self.canvas_height,
-                0.0,
-                0.0,
-                0.0,
-                0.0,
-                1.0,
-                0.0,
-                0.0,
-                0.0,
-                0.0,
-                1.0,
+                sx, 0.0, 0.0, 0.0, 0.0, -sy, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, ox, oy, 0.0, 1.0,
             ],
-        );
-
-        // Bind vertex buffer
-        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.vertex_buffer));
-
-        // Enable attributes
-        let position_loc = gl.get_attrib_location(&self.program, "aPosition") as u32;
-        let point_size_loc = gl.get_attrib_location(&self.program, "aPointSize") as u32;
+        )?;
+        program.set_float("uPointScale", layout.point_scale(&self.viewport))?;
 
-        gl.enable_vertex_attrib_array(position_loc);
-        gl.enable_vertex_attrib_array(point_size_loc);
-
-        // Set attribute pointers
-        gl.vertex_attrib_pointer_with_i32(position_loc, 2, GL::FLOAT, false, 28, 0);
-        gl.vertex_attrib_pointer_with_i32(point_size_loc, 1, GL::FLOAT, false, 28, 24);
-
-        // Create vertex data for all particles
-        let mut vertex_data = Vec::with_capacity(self.particles.len() * 7);
+        if let Some(gpu) = &self.gpu {
+            gpu.draw(self.color_mode == ColorMode::Fade);
+            return Ok(());
+        }
+        // Fading is already in the colour, the alpha only counts on the GPU
+        gl.vertex_attrib1f(program.attribute("aAlpha")?, 255.0);
+
+        let [min_x, min_y, max_x, max_y] = self.shapes[self.shape_index].bounds();
+        let scale = ColorScale {
+            center: [(min_x + max_x) / 2.0, (min_y + max_y) / 2.0],
+            radius: ((max_x - min_x).max(max_y - min_y) / 2.0).max(f32::EPSILON),
+            max_speed: self
+                .config
+                .outline_force_max
+                .max(self.config.fill_force_max)
+                .max(f32::EPSILON),
+        };
 
+        // Fill vertex data for all particles, reusing last frame's allocation
+        self.vertex_data.clear();
         for p in &self.particles {
-            // Translate to center of canvas
-            let x = p.x;
-            let y = p.y;
-
+            let [r, g, b, a] = p.color(self.color_mode, self.phase, &scale);
             // Position (x, y)
-            vertex_data.push(x);
-            vertex_data.push(y);
-
+            self.vertex_data.extend_from_slice(&[p.x, p.y]);
             // Color (r, g, b, a)
-            vertex_data.push(1.0); // r
-            vertex_data.push(0.0); // g
-            vertex_data.push(0.39); // b
-            vertex_data.push(p.alpha / 255.0); // a
-
+            self.vertex_data.extend_from_slice(&[r, g, b, a]);
             // Point size
-            vertex_data.push(p.size);
+            self.vertex_data.push(p.size);
         }
 
-        // Upload vertex data
-        let vertices_array = Float32Array::from(&vertex_data[..]);
-        gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &vertices_array, GL::DYNAMIC_DRAW);
+        // Upload vertex data into the persistent buffer, growing it only
+        // when the particles no longer fit
+        gl.bind_vertex_array(Some(&resources.vertex_array));
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&resources.buffer));
+        if self.vertex_data.len() > self.buffer_capacity {
+            self.buffer_capacity = self.vertex_data.len().next_power_of_two();
+            gl.buffer_data_with_i32(
+                GL::ARRAY_BUFFER,
+                (self.buffer_capacity * 4) as i32,
+                GL::DYNAMIC_DRAW,
+            );
+        }
+        // The view borrows wasm memory directly, nothing allocates before
+        // the upload is done with it
+        unsafe {
+            let vertices_array = Float32Array::view(&self.vertex_data);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::ARRAY_BUFFER, 0, &vertices_array);
+        }
 
         // Draw particles
         gl.draw_arrays(GL::POINTS, 0, self.particles.len() as i32);
 
         // Render text (we'll use HTML for this)
+        Ok(())
     }
 }
 
-fn setup_shaders(gl: &GL) -> Result<WebGlProgram, JsValue> {
+/// Resizes each set of points to `count`, repeating points of the smaller
+/// sets so particles merge, and orders them by angle around the origin so
+/// the i-th point of every set is in about the same place and particles
+/// morph across a shape rather than through it.
+fn match_counts(sets: Vec<Vec<[f32; 2]>>, count: usize) -> Vec<Vec<[f32; 2]>> {
+    sets.into_iter()
+        .map(|mut set| {
+            if set.is_empty() {
+                return vec![[0.0, 0.0]; count];
+            }
+            set.sort_by(|[ax, ay], [bx, by]| ay.atan2(*ax).total_cmp(&by.atan2(*bx)));
+            (0..count).map(|i| set[i * set.len() / count]).collect()
+        })
+        .collect()
+}
+
+fn create_resources(gl: &GL) -> Result<GpuResources, Error> {
+    let program = setup_shaders(gl)?;
+    program.use_program();
+    let vertex_array = gl.create_vertex_array().ok_or(Error::CreateObject {
+        object: "vertex array",
+    })?;
+    gl.bind_vertex_array(Some(&vertex_array));
+    let buffer = gl
+        .create_buffer()
+        .ok_or(Error::CreateObject { object: "buffer" })?;
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+
+    // Set up the particle layout once, the buffer is filled when drawing
+    let position_loc = program.attribute("aPosition")?;
+    let color_loc = program.attribute("aColor")?;
+    let point_size_loc = program.attribute("aPointSize")?;
+    gl.enable_vertex_attrib_array(position_loc);
+    gl.enable_vertex_attrib_array(color_loc);
+    gl.enable_vertex_attrib_array(point_size_loc);
+    gl.vertex_attrib_pointer_with_i32(position_loc, 2, GL::FLOAT, false, VERTEX_STRIDE, 0);
+    gl.vertex_attrib_pointer_with_i32(color_loc, 4, GL::FLOAT, false, VERTEX_STRIDE, 8);
+    gl.vertex_attrib_pointer_with_i32(point_size_loc, 1, GL::FLOAT, false, VERTEX_STRIDE, 24);
+
+    // Particles fade out over each other and the background
+    gl.enable(GL::BLEND);
+    gl.blend_func(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA);
+
+    Ok(GpuResources {
+        program,
+        vertex_array,
+        buffer,
+    })
+}
+
+fn setup_shaders(gl: &GL) -> Result<Program, Error> {
     // Vertex shader
     let vertex_shader_source = r#"#version 300 es
         in vec2 aPosition;
+        in vec4 aColor;
+        // 0 to 255 as in `Particle`, scaling the alpha of `aColor`
+        in float aAlpha;
         in float aPointSize;
-        uniform mat4 uTransform;        
+        uniform mat4 uTransform;
+        uniform float uPointScale;
+        out vec4 vColor;
         
         void main() {
             gl_Position = uTransform * vec4(aPosition, 0.0, 1.0);
-            gl_PointSize = aPointSize;
+            gl_PointSize = aPointSize * uPointScale;
+            vColor = vec4(aColor.rgb, aColor.a * aAlpha / 255.0);
         }
     "#;
 
     // Fragment shader
     let fragment_shader_source = r#"#version 300 es
         precision mediump float;
+        in vec4 vColor;
         out vec4 outColor;
         uniform vec3 color_multiplier;
 
         void main() {
             // Create circular points
             vec2 center = vec2(0.5, 0.5);
-            outColor = vec4(
-                color_multiplier.r,
-                color_multiplier.g,
-                color_multiplier.b,
-                1.0
-            );
+            outColor = vec4(color_multiplier, 1.0) * vColor;
             float dist = distance(gl_PointCoord, center);
             if (dist > 0.5) {
                 discard;
@@ -406,42 +1058,7 @@ fn setup_shaders(gl: &GL) -> Result<WebGlProgram, JsValue> {
         }
     "#;
 
-    let vert_shader = match compile_shader(gl, GL::VERTEX_SHADER, vertex_shader_source) {
-        Ok(shader) => shader,
-        Err(e) => {
-            console_error!("Vertex shader compilation failed: {}", e);
-            return Err(JsValue::from_str(&e));
-        }
-    };
-
-    let frag_shader = match compile_shader(gl, GL::FRAGMENT_SHADER, fragment_shader_source) {
-        Ok(shader) => shader,
-        Err(e) => {
-            console_error!("Fragment shader compilation failed: {}", e);
-            return Err(JsValue::from_str(&e));
-        }
-    };
-
-    let program = gl
-        .create_program()
-        .ok_or("Unable to create shader program")?;
-    gl.attach_shader(&program, &vert_shader);
-    gl.attach_shader(&program, &frag_shader);
-    gl.link_program(&program);
-
-    // Check for errors
-    if !gl
-        .get_program_parameter(&program, GL::LINK_STATUS)
-        .as_bool()
-        .unwrap_or(false)
-    {
-        let error = gl
-            .get_program_info_log(&program)
-            .unwrap_or_else(|| "Unknown error creating program".to_string());
-        return Err(JsValue::from_str(&error));
-    }
-
-    Ok(program)
+    Program::new(gl, vertex_shader_source, fragment_shader_source)
 }
 
 #[wasm_bindgen(start)]
@@ -452,75 +1069,582 @@ pub fn start() -> Result<(), JsValue> {
     Ok(())
 }
 
-// Animation controller
-#[wasm_bindgen]
-pub struct HeartController {
+/// The Heartbeat art, shared by the `HeartController` class and its loop.
+struct Heartbeat {
     animation: Rc<RefCell<HeartAnimation>>,
-    performance: Performance,
+    surface: Surface,
+    context: ContextWatch,
+    /// Last colour set, re-applied when a lost context is restored.
+    color: [f32; 3],
+    layout: Layout,
+    clock: Clock,
+    simulation: Simulation,
+    /// Number of particles in the GPU simulation.
+    particle_count: u32,
+    listeners: Listeners,
 }
 
-#[wasm_bindgen]
-impl HeartController {
-    #[wasm_bindgen(constructor)]
-    pub fn new(width: u32, height: u32) -> Result<HeartController, JsValue> {
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas = document
-            .get_element_by_id("canvas")
-            .unwrap()
-            .dyn_into::<HtmlCanvasElement>()?;
+/// JS callbacks registered on the heart.
+#[derive(Clone, Default)]
+struct Listeners {
+    on_beat: Option<Function>,
+    on_phase_change: Option<Function>,
+    on_formed: Option<Function>,
+}
 
-        canvas.set_width(width);
-        canvas.set_height(height);
+impl Heartbeat {
+    fn new(surface: Surface, width: u32, height: u32) -> Result<Heartbeat, Error> {
+        Heartbeat::with_config(surface, width, height, HeartbeatConfig::new())
+    }
 
-        // Get WebGL context
-        let gl = canvas.get_context("webgl2")?.unwrap().dyn_into::<GL>()?;
+    fn with_config(
+        surface: Surface,
+        width: u32,
+        height: u32,
+        config: HeartbeatConfig,
+    ) -> Result<Heartbeat, Error> {
+        config.validate()?;
 
-        let width = canvas.width() as f32;
-        let height = canvas.height() as f32;
+        // Get WebGL context
+        let viewport = Viewport::new(width as f32, height as f32, 1.0);
+        let gl = surface.webgl2_context(&viewport)?;
+        let context = ContextWatch::new(&surface)?;
 
         // Create animation
-        let animation = HeartAnimation::new(gl, width, height)?;
+        let animation = HeartAnimation::new(gl, viewport, config)?;
         let animation = Rc::new(RefCell::new(animation));
 
-        let performance = window().unwrap().performance().unwrap();
-
-        Ok(HeartController {
+        let mut controller = Heartbeat {
             animation,
-            performance,
-        })
+            surface,
+            context,
+            color: [1.0, 0.0, 0.29],
+            layout: Layout::new(400.0, 400.0),
+            // Heartbeat timing is in milliseconds
+            clock: Clock::new(FRAME_MS),
+            simulation: Simulation::Cpu,
+            particle_count: 100_000,
+            listeners: Listeners::default(),
+        };
+        let [r, g, b] = controller.color;
+        controller.set_color(r, g, b);
+        Ok(controller)
     }
+}
 
-    pub fn draw(&self) -> Result<(), JsValue> {
-        let timestamp = self.performance.now();
+impl Listeners {
+    /// Hands `events` to the JS callbacks. A callback that throws is logged
+    /// and doesn't stop the animation.
+    fn dispatch(&self, events: Vec<HeartEvent>) {
+        let report = |result: Result<JsValue, JsValue>| {
+            if let Err(e) = result {
+                console_error!("Heartbeat callback failed: {}", Error::from(e));
+            }
+        };
+        for event in events {
+            match event {
+                HeartEvent::Beat { beat, time } => {
+                    if let Some(callback) = &self.on_beat {
+                        report(callback.call2(&JsValue::NULL, &beat.into(), &time.into()));
+                    }
+                }
+                HeartEvent::Phase(phase) => {
+                    if let Some(callback) = &self.on_phase_change {
+                        report(callback.call1(&JsValue::NULL, &phase.name().into()));
+                    }
+                    if phase == Phase::Formed {
+                        if let Some(callback) = &self.on_formed {
+                            report(callback.call0(&JsValue::NULL));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Art for Heartbeat {
+    fn resize(&mut self, width: f32, height: f32, pixel_ratio: f32) {
+        let mut animation = self.animation.borrow_mut();
+        animation.viewport = Viewport::new(width, height, pixel_ratio);
+        self.surface.resize(&animation.gl, &animation.viewport);
+    }
 
+    fn draw(&mut self, timestamp: Option<f64>) -> Result<(), Error> {
+        if self.context.take_restored() {
+            self.restore();
+        }
+        if self.context.is_lost() {
+            return Ok(());
+        }
+        let mut time = self.clock.tick(timestamp);
+
+        let mut animation = self.animation.borrow_mut();
+        let catch_up = animation.sim_time() + MAX_CATCH_UP_STEPS as f64 * STEP_MS;
+        if time > catch_up {
+            time = catch_up;
+            self.clock.set_time(time);
+        }
+        animation.advance(time)?;
+        animation.render(&self.layout)
+    }
+
+    fn take_notifications(&mut self) -> Option<Box<dyn FnOnce()>> {
+        let events = std::mem::take(&mut self.animation.borrow_mut().events);
+        if events.is_empty() {
+            return None;
+        }
+        let listeners = self.listeners.clone();
+        Some(Box::new(move || listeners.dispatch(events)))
+    }
+
+    fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    fn set_time(&mut self, time: f64) {
+        self.clock.set_time(time);
+        if let Err(e) = self.animation.borrow_mut().seek(time) {
+            console_error!("{}", e);
+        }
+    }
+
+    fn seed(&self) -> Option<u32> {
+        Some(self.animation.borrow().seed)
+    }
+
+    fn reseed(&mut self, seed: u32) -> Result<(), Error> {
         {
             let mut animation = self.animation.borrow_mut();
-            animation.update(timestamp);
+            animation.seed = seed;
+            animation.resample();
         }
+        self.apply_simulation()
+    }
 
-        {
-            let animation = self.animation.borrow();
-            animation.render();
+    fn layout_mut(&mut self) -> Option<&mut Layout> {
+        Some(&mut self.layout)
+    }
+
+    fn pointer_move(&mut self, x: f32, y: f32) {
+        let mut animation = self.animation.borrow_mut();
+        animation.pointer = Some(self.layout.design_point(&animation.viewport, x, y));
+    }
+
+    /// Sets off an explosion centred on the pointer, unless the pointer is
+    /// ignored.
+    fn pointer_down(&mut self, x: f32, y: f32) {
+        let mut animation = self.animation.borrow_mut();
+        let point = self.layout.design_point(&animation.viewport, x, y);
+        animation.pointer = Some(point);
+        if animation.pointer_mode != PointerMode::Off {
+            animation.click = Some(point);
+        }
+    }
+
+    fn pointer_leave(&mut self) {
+        self.animation.borrow_mut().pointer = None;
+    }
+
+    fn release(&mut self) {
+        let mut animation = self.animation.borrow_mut();
+        if let Some(gpu) = animation.gpu.take() {
+            gpu.delete();
+        }
+        if let Some(resources) = animation.resources.take() {
+            resources.delete(&animation.gl);
         }
+    }
 
+    fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.color = [r, g, b];
+        if self.context.is_lost() {
+            return;
+        }
+        let animation = self.animation.borrow();
+        let Some(resources) = &animation.resources else {
+            return;
+        };
+        // The GPU simulation may have left its update program in use
+        resources.program.use_program();
+        if let Err(e) = resources.program.set_vec3("color_multiplier", [r, g, b]) {
+            console_error!("{}", e);
+        }
+    }
+}
+
+art_class!(
+    /// The Heartbeat animation.
+    HeartController(Heartbeat),
+    Heartbeat::new
+);
+layout_bindings!(HeartController);
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[wasm_bindgen]
+impl HeartController {
+    /// Builds the heart on `canvas` from `config`, e.g. with a seed to
+    /// reproduce a layout. A field out of range throws an `InvalidConfig`
+    /// error.
+    pub fn with_config(
+        canvas: HtmlCanvasElement,
+        width: u32,
+        height: u32,
+        config: &HeartbeatConfig,
+    ) -> Result<HeartController, Error> {
+        let heart = Heartbeat::with_config(canvas.into(), width, height, *config)?;
+        Ok(HeartController {
+            art: Rc::new(RefCell::new(heart)),
+            render_loop: RenderLoop::default(),
+        })
+    }
+
+    /// Runs the particles on the CPU (the default) or the GPU. Switching
+    /// replays the animation up to the current time in the new mode, except
+    /// that the GPU simulation, like any seek in GPU mode, starts over from
+    /// scattered particles at the current time instead of replaying.
+    pub fn set_simulation(&self, simulation: Simulation) -> Result<(), Error> {
+        let mut heart = self.art.borrow_mut();
+        heart.simulation = simulation;
+        heart.apply_simulation()
+    }
+
+    /// Number of particles in the GPU simulation, 100000 by default and at
+    /// most `MAX_GPU_PARTICLES`. The CPU simulation always uses its own
+    /// hand-spaced set.
+    pub fn set_particle_count(&self, count: u32) -> Result<(), Error> {
+        if !(1..=MAX_GPU_PARTICLES).contains(&count) {
+            return Err(Error::InvalidConfig {
+                field: "particle_count",
+                reason: format!("must be between 1 and {}", MAX_GPU_PARTICLES),
+            });
+        }
+        let mut heart = self.art.borrow_mut();
+        heart.particle_count = count;
+        if heart.simulation == Simulation::Gpu {
+            heart.apply_simulation()?;
+        }
         Ok(())
     }
-    pub fn set_color(&self, r: f32, g: f32, b: f32) {
-        let gl = &self.animation.borrow().gl;
-        match gl.get_uniform_location(&self.animation.borrow().program, "color_multiplier") {
-            Some(color_location) => {
-                gl.uniform3f(Some(&color_location), r, g, b);
+
+    /// The config in use, with the current seed.
+    pub fn config(&self) -> HeartbeatConfig {
+        let heart = self.art.borrow();
+        let animation = heart.animation.borrow();
+        HeartbeatConfig {
+            seed: Some(animation.seed),
+            ..animation.config
+        }
+    }
+
+    /// Applies `config` from the next step on. Changes to the seed, spacing,
+    /// size or outline points rebuild the particles and replay up to the
+    /// current time; an unset seed keeps the current one. A field out of range throws an `InvalidConfig` error and leaves
+    /// the config as it was.
+    pub fn set_config(&self, config: &HeartbeatConfig) -> Result<(), Error> {
+        config.validate()?;
+        let mut heart = self.art.borrow_mut();
+        let reshapes = {
+            let mut animation = heart.animation.borrow_mut();
+            let reseeds = config.seed.is_some_and(|seed| seed != animation.seed);
+            let reshapes = reseeds || animation.config.reshapes(config);
+            if let Some(seed) = config.seed {
+                animation.seed = seed;
             }
-            None => {
-                console_error!("color_multiplier uniform not found")
+            animation.config = *config;
+            if reshapes {
+                animation.resample();
+            }
+            reshapes
+        };
+        if reshapes {
+            heart.apply_simulation()?;
+        }
+        Ok(())
+    }
+
+    /// Forms `shape` instead of the heart, rebuilding the particles and
+    /// replaying up to the current time.
+    pub fn set_shape(&self, shape: &Shape) -> Result<(), Error> {
+        self.set_shapes(vec![shape.clone()])
+    }
+
+    /// Morphs through `shapes` in turn, one per beat, back to the first
+    /// after the last. An empty list goes back to the heart.
+    pub fn set_shapes(&self, shapes: Vec<Shape>) -> Result<(), Error> {
+        let mut heart = self.art.borrow_mut();
+        {
+            let mut animation = heart.animation.borrow_mut();
+            animation.shapes = if shapes.is_empty() {
+                vec![Shape::heart()]
+            } else {
+                shapes
+            };
+            animation.resample();
+        }
+        heart.apply_simulation()
+    }
+
+    /// Beats per minute, taking effect on the next beat.
+    pub fn set_heart_rate(&self, bpm: f32) -> Result<(), Error> {
+        let heart = self.art.borrow();
+        let mut animation = heart.animation.borrow_mut();
+        let config = HeartbeatConfig {
+            heart_rate: bpm,
+            ..animation.config
+        };
+        config.validate()?;
+        animation.config = config;
+        Ok(())
+    }
+
+    /// Beats to a recording from now on, one sample per line of `csv`,
+    /// instead of the heart rate.
+    pub fn load_rhythm_csv(&self, csv: &str, unit: RhythmUnit) -> Result<(), Error> {
+        let mut heart = self.art.borrow_mut();
+        let rhythm = Rhythm::from_csv(csv, unit, heart.clock.time())?;
+        heart.set_rhythm(Some(rhythm));
+        Ok(())
+    }
+
+    /// Same as `load_rhythm_csv` for a JSON array of numbers.
+    pub fn load_rhythm_json(&self, json: &str, unit: RhythmUnit) -> Result<(), Error> {
+        let mut heart = self.art.borrow_mut();
+        let rhythm = Rhythm::from_json(json, unit, heart.clock.time())?;
+        heart.set_rhythm(Some(rhythm));
+        Ok(())
+    }
+
+    /// Appends a sample as it arrives from a live source, starting an empty
+    /// recording at the current time if none is loaded. The heart holds its
+    /// shape while it waits for more.
+    pub fn push_rhythm_sample(&self, value: f64, unit: RhythmUnit) -> Result<(), Error> {
+        let heart = self.art.borrow();
+        let start = heart.clock.time();
+        let mut animation = heart.animation.borrow_mut();
+        animation
+            .rhythm
+            .get_or_insert_with(|| Rhythm::new(start))
+            .push(value, unit)
+    }
+
+    /// Plays the recording again once it ends, off by default.
+    pub fn set_rhythm_looping(&self, looping: bool) {
+        let heart = self.art.borrow();
+        let mut animation = heart.animation.borrow_mut();
+        if let Some(rhythm) = &mut animation.rhythm {
+            rhythm.looping = looping;
+        }
+    }
+
+    /// Playback speed of the recording from now on, 1.0 being real time.
+    /// Seeking back to before the change plays at the old speed again.
+    pub fn set_rhythm_speed(&self, speed: f64) {
+        let heart = self.art.borrow();
+        let mut animation = heart.animation.borrow_mut();
+        if let Some(rhythm) = &mut animation.rhythm {
+            rhythm.set_speed(heart.clock.time(), speed);
+        }
+    }
+
+    /// Goes back to beating at the configured heart rate.
+    pub fn clear_rhythm(&self) {
+        let mut heart = self.art.borrow_mut();
+        heart.set_rhythm(None);
+    }
+
+    /// Calls `callback(beat, time)` on every beat, `beat` counting from 1
+    /// and `time` being the animation time in milliseconds. `undefined`
+    /// removes it.
+    pub fn set_on_beat(&self, callback: Option<Function>) {
+        let mut heart = self.art.borrow_mut();
+        heart.listeners.on_beat = callback;
+    }
+
+    /// Calls `callback(phase)` whenever the particles move on to another
+    /// phase: `"Formed"`, `"Exploding"` or `"Reforming"`. They start out
+    /// `"Initializing"`, and a seek, reseed or reshape landing in another
+    /// phase reports it on the next `draw`.
+    pub fn set_on_phase_change(&self, callback: Option<Function>) {
+        let mut heart = self.art.borrow_mut();
+        heart.listeners.on_phase_change = callback;
+    }
+
+    /// Calls `callback()` each time the particles have settled into the
+    /// shape, first from their scattered start, then after every beat.
+    pub fn set_on_formed(&self, callback: Option<Function>) {
+        let mut heart = self.art.borrow_mut();
+        heart.listeners.on_formed = callback;
+    }
+
+    /// What the pointer does to the particles near it, `PointerMode::Repel`
+    /// by default.
+    pub fn set_pointer_mode(&self, mode: PointerMode) {
+        let heart = self.art.borrow();
+        let mut animation = heart.animation.borrow_mut();
+        animation.pointer_mode = mode;
+        if mode == PointerMode::Off {
+            animation.click = None;
+        }
+    }
+
+    /// Shades the particles by `mode`, `ColorMode::Fade` by default. The GPU
+    /// simulation only fades them, and draws them in the plain colour in the
+    /// other modes.
+    pub fn set_color_mode(&self, mode: ColorMode) {
+        let heart = self.art.borrow();
+        heart.animation.borrow_mut().color_mode = mode;
+    }
+}
+
+impl Heartbeat {
+    fn set_rhythm(&mut self, rhythm: Option<Rhythm>) {
+        let mut animation = self.animation.borrow_mut();
+        animation.rhythm_index = match &rhythm {
+            Some(rhythm) => rhythm.next_after(animation.sim_time(), 0),
+            None => 0,
+        };
+        animation.rhythm = rhythm;
+    }
+
+    fn apply_simulation(&mut self) -> Result<(), Error> {
+        let count = match self.simulation {
+            Simulation::Cpu => None,
+            Simulation::Gpu => Some(self.particle_count as usize),
+        };
+        if count.is_some() && self.context.is_lost() {
+            // `restore` builds the GPU particles once the context is back
+            return Ok(());
+        }
+        let mut animation = self.animation.borrow_mut();
+        animation.use_gpu(count)?;
+        animation.seek(self.clock.time())
+    }
+
+    /// Rebuilds the GPU objects on a restored context, keeping the time and
+    /// colour. The CPU particles carry on from where they were; the GPU ones
+    /// went with the context and start over scattered at the current time,
+    /// as on a seek.
+    fn restore(&mut self) {
+        {
+            let mut animation = self.animation.borrow_mut();
+            animation.buffer_capacity = 0;
+            // The old GPU particles went with the context
+            animation.gpu = None;
+            animation.resources = create_resources(&animation.gl)
+                .map_err(|e| console_error!("Failed to restore WebGL resources: {}", e))
+                .ok();
+            self.surface.resize(&animation.gl, &animation.viewport);
+        }
+        if self.simulation == Simulation::Gpu {
+            if let Err(e) = self.apply_simulation() {
+                console_error!("Failed to restore the GPU simulation: {}", e);
             }
+        }
+        let [r, g, b] = self.color;
+        self.set_color(r, g, b);
+        self.clock.rebase();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen::JsCast;
+
+    /// A heart that can only be stepped, not rendered, as there is no GL
+    /// context outside the browser.
+    fn animation(seed: u32) -> HeartAnimation {
+        let config = HeartbeatConfig {
+            seed: Some(seed),
+            ..HeartbeatConfig::new()
         };
+        let viewport = Viewport::new(400.0, 400.0, 1.0);
+        HeartAnimation::with_resources(JsValue::NULL.unchecked_into(), None, viewport, config)
     }
 
-    pub fn stop(&mut self) {}
+    fn positions(animation: &HeartAnimation) -> Vec<[f32; 2]> {
+        animation.particles.iter().map(|p| [p.x, p.y]).collect()
+    }
 
-    #[wasm_bindgen]
-    pub fn destroy(&mut self) {
-        self.stop();
+    #[test]
+    fn same_seed_and_time_give_the_same_particles() {
+        let mut once = animation(7);
+        once.advance(5000.0).unwrap();
+
+        // Uneven frames, as on a display at another refresh rate
+        let mut frames = animation(7);
+        for time in [10.0, 40.0, 41.0, 1234.5, 3000.0, 5000.0] {
+            frames.advance(time).unwrap();
+        }
+        assert_eq!(positions(&frames), positions(&once));
+
+        let mut seeked = animation(7);
+        seeked.advance(8000.0).unwrap();
+        seeked.seek(5000.0).unwrap();
+        assert_eq!(positions(&seeked), positions(&once));
+
+        let mut other = animation(8);
+        other.advance(5000.0).unwrap();
+        assert_ne!(positions(&other), positions(&once));
+    }
+
+    #[test]
+    fn seek_reports_a_phase_change() {
+        let mut heart = animation(7);
+        heart.advance(8000.0).unwrap();
+        assert!(heart.phase != Phase::Initializing);
+        heart.seek(0.0).unwrap();
+        assert!(matches!(
+            heart.events[..],
+            [HeartEvent::Phase(Phase::Initializing)]
+        ));
+    }
+
+    #[test]
+    fn validate_rejects_sizes_out_of_range() {
+        let field = |config: HeartbeatConfig| match config.validate() {
+            Err(Error::InvalidConfig { field, .. }) => Some(field),
+            _ => None,
+        };
+        assert_eq!(field(HeartbeatConfig::new()), None);
+        let config = HeartbeatConfig {
+            outline_points: u32::MAX,
+            ..HeartbeatConfig::new()
+        };
+        assert_eq!(field(config), Some("outline_points"));
+        let config = HeartbeatConfig {
+            spacing: MIN_SPACING / 2.0,
+            ..HeartbeatConfig::new()
+        };
+        assert_eq!(field(config), Some("spacing"));
+    }
+
+    #[test]
+    fn match_counts_resizes_and_orders_by_angle() {
+        let sets = vec![
+            vec![[0.0, -1.0], [1.0, 0.0], [-1.0, 0.0], [0.0, 1.0]],
+            vec![[1.0, 1.0], [-1.0, -1.0]],
+            Vec::new(),
+        ];
+        let matched = match_counts(sets, 4);
+        assert_eq!(
+            matched[0],
+            [[0.0, -1.0], [1.0, 0.0], [0.0, 1.0], [-1.0, 0.0]]
+        );
+        assert_eq!(
+            matched[1],
+            [[-1.0, -1.0], [-1.0, -1.0], [1.0, 1.0], [1.0, 1.0]]
+        );
+        assert_eq!(matched[2], [[0.0, 0.0]; 4]);
     }
 }
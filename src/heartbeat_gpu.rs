@@ -0,0 +1,356 @@
+//! Heartbeat particles simulated on the GPU with transform feedback, for
+//! particle counts the CPU path in `heartbeat.rs` can't keep up with. The
+//! update shader mirrors `Particle::update` and `Particle::explode`, which
+//! remain the reference for the physics.
+
+use crate::error::Error;
+use crate::heartbeat::HeartbeatConfig;
+use crate::program::Program;
+use web_sys::{
+    WebGl2RenderingContext as GL, WebGlBuffer, WebGlTransformFeedback, WebGlVertexArrayObject,
+};
+
+/// Starting state of a particle, as the CPU simulation builds it.
+pub struct ParticleInit {
+    pub x: f32,
+    pub y: f32,
+    pub target_x: f32,
+    pub target_y: f32,
+    pub is_outline: bool,
+    pub size: f32,
+    pub reform_speed: f32,
+    pub initial_speed: f32,
+    /// Uniform in 0..1, seeds the particle's explosion force.
+    pub random: f32,
+}
+
+/// Per-particle state written by transform feedback: x, y, vx, vy, alpha.
+const STATE_FLOATS: usize = 5;
+const STATE_STRIDE: i32 = (STATE_FLOATS * 4) as i32;
+
+/// Per-particle constants: target x, y, reform speed, initial speed,
+/// outline flag, random, size.
+const CONSTANT_FLOATS: usize = 7;
+const CONSTANT_STRIDE: i32 = (CONSTANT_FLOATS * 4) as i32;
+
+const UPDATE_VERTEX_SHADER: &str = r#"#version 300 es
+    in vec2 aPosition;
+    in vec2 aVelocity;
+    in float aAlpha;
+    in vec2 aTarget;
+    // reform speed, initial speed, outline flag, random
+    in vec4 aParams;
+
+    // 0 initializing, 1 formed, 2 exploding, 3 reforming
+    uniform int uPhase;
+    uniform bool uExplode;
+    uniform vec2 uExplodeCenter;
+    uniform float uBeat;
+    // min and max explosion force
+    uniform vec2 uOutlineForce;
+    uniform vec2 uFillForce;
+    uniform float uGravity;
+    uniform float uDamping;
+    uniform vec2 uPointer;
+    // positive pushes away, negative pulls in, 0 when there's no pointer
+    uniform float uPointerForce;
+    uniform float uPointerRadius;
+
+    out vec2 vPosition;
+    out vec2 vVelocity;
+    out float vAlpha;
+
+    float hash(float n) {
+        return fract(sin(n) * 43758.5453);
+    }
+
+    void main() {
+        vec2 position = aPosition;
+        vec2 velocity = aVelocity;
+        float alpha = aAlpha;
+
+        if (uExplode) {
+            vec2 away = position - uExplodeCenter;
+            float angle = atan(away.y, away.x);
+            float r = hash(aParams.w * 1000.0 + uBeat);
+            vec2 range = aParams.z > 0.5 ? uOutlineForce : uFillForce;
+            float force = mix(range.x, range.y, r);
+            velocity = vec2(cos(angle), sin(angle)) * force;
+        }
+
+        if (uPhase == 0) {
+            position += (aTarget - position) * aParams.y;
+        } else if (uPhase == 1) {
+            position += (aTarget - position) * aParams.x;
+        } else if (uPhase == 2) {
+            position += velocity;
+            velocity.y += uGravity;
+            velocity *= uDamping;
+            alpha = max(alpha - 1.0, 100.0);
+        } else if (uPhase == 3) {
+            position += (aTarget - position) * aParams.x;
+            alpha = min(alpha + 5.0, 255.0);
+        }
+
+        vec2 fromPointer = position - uPointer;
+        float distance = length(fromPointer);
+        if (uPointerForce != 0.0 && distance > 0.0 && distance < uPointerRadius) {
+            float push = max(uPointerForce * (1.0 - distance / uPointerRadius), -distance);
+            position += fromPointer / distance * push;
+        }
+
+        vPosition = position;
+        vVelocity = velocity;
+        vAlpha = alpha;
+    }
+"#;
+
+const UPDATE_FRAGMENT_SHADER: &str = r#"#version 300 es
+    precision mediump float;
+    out vec4 outColor;
+
+    void main() {
+        outColor = vec4(0.0);
+    }
+"#;
+
+pub struct GpuParticles {
+    gl: GL,
+    update: Program,
+    count: i32,
+    /// Ping-pong state buffers, read from `current` and written to the other.
+    states: [WebGlBuffer; 2],
+    constants: WebGlBuffer,
+    /// Vertex arrays reading `states[i]`, for the update and the render program.
+    update_arrays: [WebGlVertexArrayObject; 2],
+    render_arrays: [WebGlVertexArrayObject; 2],
+    feedback: WebGlTransformFeedback,
+    /// Contents of `constants`, kept to re-target the particles.
+    constant_data: Vec<f32>,
+    /// `aColor` and `aAlpha` of the render program.
+    color_location: u32,
+    alpha_location: u32,
+    current: usize,
+    initial_state: Vec<f32>,
+}
+
+impl GpuParticles {
+    /// Uploads `particles` and wires their buffers to `render`, which reads
+    /// `aPosition`, `aAlpha` and `aPointSize`.
+    pub fn new(gl: &GL, render: &Program, particles: &[ParticleInit]) -> Result<Self, Error> {
+        let update = Program::with_feedback(
+            gl,
+            UPDATE_VERTEX_SHADER,
+            UPDATE_FRAGMENT_SHADER,
+            &["vPosition", "vVelocity", "vAlpha"],
+        )?;
+
+        let mut initial_state = Vec::with_capacity(particles.len() * STATE_FLOATS);
+        let mut constant_data = Vec::with_capacity(particles.len() * CONSTANT_FLOATS);
+        for p in particles {
+            initial_state.extend_from_slice(&[p.x, p.y, 0.0, 0.0, 255.0]);
+            constant_data.extend_from_slice(&[
+                p.target_x,
+                p.target_y,
+                p.reform_speed,
+                p.initial_speed,
+                if p.is_outline { 1.0 } else { 0.0 },
+                p.random,
+                p.size,
+            ]);
+        }
+
+        let create_buffer = || {
+            gl.create_buffer()
+                .ok_or(Error::CreateObject { object: "buffer" })
+        };
+        let create_vertex_array = || {
+            gl.create_vertex_array().ok_or(Error::CreateObject {
+                object: "vertex array",
+            })
+        };
+
+        let states = [create_buffer()?, create_buffer()?];
+        for state in &states {
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(state));
+            gl.buffer_data_with_i32(
+                GL::ARRAY_BUFFER,
+                (initial_state.len() * 4) as i32,
+                GL::DYNAMIC_COPY,
+            );
+        }
+
+        let constants = create_buffer()?;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&constants));
+        unsafe {
+            let constants_array = js_sys::Float32Array::view(&constant_data);
+            gl.buffer_data_with_array_buffer_view(
+                GL::ARRAY_BUFFER,
+                &constants_array,
+                GL::STATIC_DRAW,
+            );
+        }
+
+        let update_arrays = [create_vertex_array()?, create_vertex_array()?];
+        let render_arrays = [create_vertex_array()?, create_vertex_array()?];
+        for i in 0..2 {
+            gl.bind_vertex_array(Some(&update_arrays[i]));
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&states[i]));
+            attribute(gl, update.attribute("aPosition")?, 2, STATE_STRIDE, 0);
+            attribute(gl, update.attribute("aVelocity")?, 2, STATE_STRIDE, 8);
+            attribute(gl, update.attribute("aAlpha")?, 1, STATE_STRIDE, 16);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&constants));
+            attribute(gl, update.attribute("aTarget")?, 2, CONSTANT_STRIDE, 0);
+            attribute(gl, update.attribute("aParams")?, 4, CONSTANT_STRIDE, 8);
+
+            gl.bind_vertex_array(Some(&render_arrays[i]));
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&states[i]));
+            attribute(gl, render.attribute("aPosition")?, 2, STATE_STRIDE, 0);
+            attribute(gl, render.attribute("aAlpha")?, 1, STATE_STRIDE, 16);
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&constants));
+            attribute(gl, render.attribute("aPointSize")?, 1, CONSTANT_STRIDE, 24);
+        }
+        gl.bind_vertex_array(None);
+        // A buffer can't stay bound here while transform feedback writes it
+        gl.bind_buffer(GL::ARRAY_BUFFER, None);
+
+        let feedback = gl.create_transform_feedback().ok_or(Error::CreateObject {
+            object: "transform feedback",
+        })?;
+
+        let mut gpu = GpuParticles {
+            gl: gl.clone(),
+            update,
+            count: particles.len() as i32,
+            states,
+            constants,
+            update_arrays,
+            render_arrays,
+            feedback,
+            constant_data,
+            color_location: render.attribute("aColor")?,
+            alpha_location: render.attribute("aAlpha")?,
+            current: 0,
+            initial_state,
+        };
+        gpu.reset();
+        Ok(gpu)
+    }
+
+    /// Puts the particles back where they started.
+    pub fn reset(&mut self) {
+        let gl = &self.gl;
+        self.current = 0;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.states[0]));
+        unsafe {
+            let state_array = js_sys::Float32Array::view(&self.initial_state);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::ARRAY_BUFFER, 0, &state_array);
+        }
+        gl.bind_buffer(GL::ARRAY_BUFFER, None);
+    }
+
+    /// Moves the particles' targets to `targets`, one per particle.
+    pub fn retarget(&mut self, targets: &[[f32; 2]]) {
+        for (constants, [x, y]) in self.constant_data.chunks_mut(CONSTANT_FLOATS).zip(targets) {
+            constants[0] = *x;
+            constants[1] = *y;
+        }
+        let gl = &self.gl;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.constants));
+        unsafe {
+            let constants_array = js_sys::Float32Array::view(&self.constant_data);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(
+                GL::ARRAY_BUFFER,
+                0,
+                &constants_array,
+            );
+        }
+        gl.bind_buffer(GL::ARRAY_BUFFER, None);
+    }
+
+    /// Advances every particle by one fixed physics step in `phase`, the
+    /// `Phase` discriminant. `explode` first sends the particles flying
+    /// from that centre, for beat number `beat`. `pointer` is the pointer
+    /// position with the strength it pushes the particles away with.
+    pub fn step(
+        &mut self,
+        phase: i32,
+        explode: Option<[f32; 2]>,
+        beat: u32,
+        config: &HeartbeatConfig,
+        pointer: Option<([f32; 2], f32)>,
+    ) -> Result<(), Error> {
+        let gl = &self.gl;
+        let next = 1 - self.current;
+
+        self.update.use_program();
+        self.update.set_int("uPhase", phase)?;
+        self.update.set_int("uExplode", explode.is_some() as i32)?;
+        self.update
+            .set_vec2("uExplodeCenter", explode.unwrap_or_default())?;
+        self.update.set_float("uBeat", beat as f32)?;
+        self.update.set_vec2(
+            "uOutlineForce",
+            [config.outline_force_min, config.outline_force_max],
+        )?;
+        self.update
+            .set_vec2("uFillForce", [config.fill_force_min, config.fill_force_max])?;
+        self.update.set_float("uGravity", config.gravity)?;
+        self.update.set_float("uDamping", config.damping)?;
+        let (position, force) = pointer.unwrap_or_default();
+        self.update.set_vec2("uPointer", position)?;
+        self.update.set_float("uPointerForce", force)?;
+        self.update
+            .set_float("uPointerRadius", config.pointer_radius)?;
+
+        gl.bind_vertex_array(Some(&self.update_arrays[self.current]));
+        gl.bind_transform_feedback(GL::TRANSFORM_FEEDBACK, Some(&self.feedback));
+        gl.bind_buffer_base(GL::TRANSFORM_FEEDBACK_BUFFER, 0, Some(&self.states[next]));
+
+        gl.enable(GL::RASTERIZER_DISCARD);
+        gl.begin_transform_feedback(GL::POINTS);
+        gl.draw_arrays(GL::POINTS, 0, self.count);
+        gl.end_transform_feedback();
+        gl.disable(GL::RASTERIZER_DISCARD);
+
+        gl.bind_buffer_base(GL::TRANSFORM_FEEDBACK_BUFFER, 0, None);
+        gl.bind_transform_feedback(GL::TRANSFORM_FEEDBACK, None);
+        gl.bind_vertex_array(None);
+
+        self.current = next;
+        Ok(())
+    }
+
+    /// Draws the particles with the render program, which must be in use.
+    /// Its `aColor` isn't fed from a buffer and reads as opaque white, faded
+    /// by the particles' alpha if `fade` is set.
+    pub fn draw(&self, fade: bool) {
+        let gl = &self.gl;
+        gl.bind_vertex_array(Some(&self.render_arrays[self.current]));
+        gl.vertex_attrib4f(self.color_location, 1.0, 1.0, 1.0, 1.0);
+        if fade {
+            gl.enable_vertex_attrib_array(self.alpha_location);
+        } else {
+            gl.disable_vertex_attrib_array(self.alpha_location);
+            gl.vertex_attrib1f(self.alpha_location, 255.0);
+        }
+        gl.draw_arrays(GL::POINTS, 0, self.count);
+    }
+
+    pub fn delete(self) {
+        let gl = &self.gl;
+        for i in 0..2 {
+            gl.delete_vertex_array(Some(&self.update_arrays[i]));
+            gl.delete_vertex_array(Some(&self.render_arrays[i]));
+            gl.delete_buffer(Some(&self.states[i]));
+        }
+        gl.delete_buffer(Some(&self.constants));
+        gl.delete_transform_feedback(Some(&self.feedback));
+        self.update.delete();
+    }
+}
+
+fn attribute(gl: &GL, location: u32, size: i32, stride: i32, offset: i32) {
+    gl.enable_vertex_attrib_array(location);
+    gl.vertex_attrib_pointer_with_i32(location, size, GL::FLOAT, false, stride, offset);
+}
@@ -19,12 +19,18 @@ macro_rules! console_error {
     ($($t:tt)*) => (error(&format!($($t)*)))
 }
 
-use web_sys::{WebGl2RenderingContext as GL, WebGlProgram, WebGlShader};
+use crate::error::{Error, ShaderStage};
+use crate::program::Program;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    HtmlCanvasElement, Performance, WebGl2RenderingContext as GL, WebGlBuffer, WebGlProgram,
+    WebGlShader, WebGlVertexArrayObject,
+};
 
-pub fn compile_shader(gl: &GL, shader_type: u32, source: &str) -> Result<WebGlShader, String> {
+pub fn compile_shader(gl: &GL, shader_type: u32, source: &str) -> Result<WebGlShader, Error> {
     let shader = gl
         .create_shader(shader_type)
-        .ok_or_else(|| String::from("error creating shader"))?;
+        .ok_or(Error::CreateObject { object: "shader" })?;
     gl.shader_source(&shader, source);
     gl.compile_shader(&shader);
 
@@ -35,25 +41,39 @@ pub fn compile_shader(gl: &GL, shader_type: u32, source: &str) -> Result<WebGlSh
     {
         Ok(shader)
     } else {
-        Err(gl
-            .get_shader_info_log(&shader)
-            .unwrap_or_else(|| String::from("error creating shader")))
+        let log = gl.get_shader_info_log(&shader).unwrap_or_default();
+        gl.delete_shader(Some(&shader));
+        Err(Error::ShaderCompile {
+            stage: ShaderStage::from_gl(shader_type),
+            log,
+        })
     }
 }
 
-pub fn link_program(
+/// Links a program whose vertex shader outputs `varyings` are captured,
+/// interleaved, by transform feedback.
+pub fn link_program_with_varyings(
     gl: &GL,
     vert_shader: &WebGlShader,
     frag_shader: &WebGlShader,
-) -> Result<WebGlProgram, String> {
+    varyings: &[&str],
+) -> Result<WebGlProgram, Error> {
     let program = gl
         .create_program()
-        .ok_or_else(|| String::from("error creating shader instance"))?;
+        .ok_or(Error::CreateObject { object: "program" })?;
 
     gl.attach_shader(&program, vert_shader);
     gl.attach_shader(&program, frag_shader);
+    if !varyings.is_empty() {
+        let names: js_sys::Array = varyings.iter().map(|&name| JsValue::from(name)).collect();
+        gl.transform_feedback_varyings(&program, &names, GL::INTERLEAVED_ATTRIBS);
+    }
     gl.link_program(&program);
 
+    // Only flags the shaders, they are freed together with the program
+    gl.delete_shader(Some(vert_shader));
+    gl.delete_shader(Some(frag_shader));
+
     if gl
         .get_program_parameter(&program, GL::LINK_STATUS)
         .as_bool()
@@ -61,8 +81,58 @@ pub fn link_program(
     {
         Ok(program)
     } else {
-        Err(gl
-            .get_program_info_log(&program)
-            .unwrap_or_else(|| String::from("error creating object")))
+        let log = gl.get_program_info_log(&program).unwrap_or_default();
+        gl.delete_program(Some(&program));
+        Err(Error::ShaderLink { log })
+    }
+}
+
+/// GPU objects an art draws with, deleted explicitly since WebGL only frees
+/// them when the context goes away.
+pub struct GpuResources {
+    pub program: Program,
+    pub vertex_array: WebGlVertexArrayObject,
+    pub buffer: WebGlBuffer,
+}
+
+impl GpuResources {
+    pub fn delete(self, gl: &GL) {
+        gl.delete_vertex_array(Some(&self.vertex_array));
+        gl.delete_buffer(Some(&self.buffer));
+        self.program.delete();
     }
 }
+
+/// Id of the canvas the arts draw on when the page doesn't hand one over.
+pub const DEFAULT_CANVAS_ID: &str = "canvas";
+
+pub fn default_canvas() -> Result<HtmlCanvasElement, Error> {
+    find_canvas(&format!("#{}", DEFAULT_CANVAS_ID))
+}
+
+pub fn find_canvas(selector: &str) -> Result<HtmlCanvasElement, Error> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or(Error::NoWindow)?;
+    document
+        .query_selector(selector)?
+        .and_then(|element| element.dyn_into::<HtmlCanvasElement>().ok())
+        .ok_or_else(|| Error::MissingCanvas {
+            selector: selector.to_string(),
+        })
+}
+
+/// Fresh seed for an art's random layout, from `Math.random()`.
+pub fn random_seed() -> u32 {
+    (js_sys::Math::random() * u32::MAX as f64) as u32
+}
+
+/// Milliseconds from `performance.now()`, read from the global scope so it
+/// works both on the page and inside a worker.
+pub fn now() -> f64 {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
+        .ok()
+        .and_then(|performance| performance.dyn_into::<Performance>().ok())
+        .map(|performance| performance.now())
+        .unwrap_or_else(js_sys::Date::now)
+}
@@ -1,173 +1,68 @@
-use crate::helpers::*;
+use crate::shader_art::{Geometry, Sketch};
+use std::f64::consts::PI;
 use wasm_bindgen::prelude::*;
-use web_sys::{
-    HtmlCanvasElement, WebGl2RenderingContext as GL, WebGlBuffer, WebGlProgram,
-    WebGlVertexArrayObject,
-};
-
-#[allow(unused)]
-#[wasm_bindgen]
-pub struct JellyFish {
-    gl: GL,
-    program: WebGlProgram,
-    vertex_array: WebGlVertexArrayObject,
-    buffer: WebGlBuffer,
-    time: f64,
-    width: u32,
-    height: u32,
-    points_count: i32,
-}
-
-#[wasm_bindgen]
-impl JellyFish {
-    #[wasm_bindgen(constructor)]
-    pub fn new(width: u32, height: u32) -> Result<Self, JsValue> {
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas = document
-            .get_element_by_id("canvas")
-            .unwrap()
-            .dyn_into::<HtmlCanvasElement>()?;
-
-        canvas.set_width(width);
-        canvas.set_height(height);
 
-        let gl = canvas.get_context("webgl2")?.unwrap().dyn_into::<GL>()?;
-
-        let vertex_shader = compile_shader(
-            &gl,
-            GL::VERTEX_SHADER,
-            r#"#version 300 es
-            in vec2 position;
-            uniform float time;
-            uniform vec2 resolution;
-            out float v_stroke;
+static SKETCH: Sketch = Sketch {
+    step: PI / 120.0,
+    geometry: Geometry::Points {
+        count: 40000,
+        vertex_shader: r#"#version 300 es
+        in vec2 position;
+        uniform float time;
+        uniform vec4 transform;
+        uniform float point_size;
+        uniform vec2 resolution;
+        uniform vec4 mouse;
+        out float v_stroke;
+        
+        float mag(float k, float e) {
+            return sqrt(k * k + e * e);
+        }
+        
+        void main() {
+            float x = position.x;
+            float y = position.y;
             
-            float mag(float k, float e) {
-                return sqrt(k * k + e * e);
-            }
+            float k = x/8.0 - 12.5;
+            float e = y/8.0 - 12.5;
+            float o = pow(mag(k, e), 2.0)/169.0;
+            float d = 0.5 + 5.0*cos(o);
             
-            void main() {
-                float x = position.x;
-                float y = position.y;
-                
-                float k = x/8.0 - 12.5;
-                float e = y/8.0 - 12.5;
-                float o = pow(mag(k, e), 2.0)/169.0;
-                float d = 0.5 + 5.0*cos(o);
-                
-                v_stroke = pow(d * sin(k) * sin(time * 4.0 + e), 2.0);
-                
-                float new_x = x + d*k*sin(d*2.0 + o + time) + e*cos(e + time) + resolution.x/4.0;
-                float new_y = o*135.0 - y/4.0 - d*6.0*cos(d*3.0 + o*9.0 + time) + resolution.y/3.2;
-                
-                gl_Position = vec4(new_x/(resolution.x/2.0) - 1.0, -(new_y/(resolution.y/2.0) - 1.0), 0, 1);
-                gl_PointSize = 1.0;
-            }
-            "#,
-        )?;
-
-        let fragment_shader = compile_shader(
-            &gl,
-            GL::FRAGMENT_SHADER,
-            r#"#version 300 es
-            precision highp float;
-            in float v_stroke;
-            uniform vec3 color_multiplier;
-            out vec4 outColor;
+            v_stroke = pow(d * sin(k) * sin(time * 4.0 + e), 2.0);
             
-            void main() {
-                outColor = vec4(
-                    v_stroke * color_multiplier.r,
-                    v_stroke * color_multiplier.g,
-                    v_stroke * color_multiplier.b,
-                    1
-                );
-            }
-            "#,
-        )?;
-
-        let program = link_program(&gl, &vertex_shader, &fragment_shader)?;
-        gl.use_program(Some(&program));
-
-        let mut vertices: Vec<f32> = Vec::with_capacity(40000 * 3);
-        for i in 0..40000 {
-            let x = (i % 200) as f32;
-            let y = (i / 200) as f32;
-            vertices.push(x);
-            vertices.push(y);
-            vertices.push(i as f32);
+            float new_x = x + d*k*sin(d*2.0 + o + time) + e*cos(e + time) + 100.0;
+            float new_y = o*135.0 - y/4.0 - d*6.0*cos(d*3.0 + o*9.0 + time) + 125.0;
+            
+            // Model space is the 400x400 design box centred on the origin, y up
+            vec2 model = vec2(new_x - 200.0, 200.0 - new_y);
+
+            // Drifts towards the pointer, the lower rows lagging behind and
+            // the pull doubling while the button is held. `pointer` is its
+            // offset from the canvas centre in model units, zero while away.
+            vec2 pointer = (mouse.xy / resolution * 2.0 - 1.0) / transform.xy;
+            float pull = (mouse.z > 0.0 ? 0.5 : 0.25) * (1.0 - y / 400.0);
+            model += pointer * pull;
+            gl_Position = vec4(model * transform.xy + transform.zw, 0, 1);
+            gl_PointSize = max(point_size, 1.0);
         }
-
-        let vertex_array = gl
-            .create_vertex_array()
-            .ok_or("Failed to create vertex array")?;
-        gl.bind_vertex_array(Some(&vertex_array));
-
-        let buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
-        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
-
-        unsafe {
-            let vert_array = js_sys::Float32Array::view(&vertices);
-            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &vert_array, GL::STATIC_DRAW);
+        "#,
+    },
+    fragment_shader: r#"#version 300 es
+        precision highp float;
+        in float v_stroke;
+        uniform vec3 color_multiplier;
+        out vec4 outColor;
+        
+        void main() {
+            outColor = vec4(
+                v_stroke * color_multiplier.r,
+                v_stroke * color_multiplier.g,
+                v_stroke * color_multiplier.b,
+                1
+            );
         }
+        "#,
+};
 
-        let position_attribute_location = gl.get_attrib_location(&program, "position") as u32;
-
-        gl.enable_vertex_attrib_array(position_attribute_location);
-        gl.vertex_attrib_pointer_with_i32(position_attribute_location, 2, GL::FLOAT, false, 12, 0);
-
-        Ok(Self {
-            points_count: 40000,
-            time: 0.0,
-            gl,
-            program,
-            vertex_array,
-            buffer,
-            width,
-            height,
-        })
-    }
-
-    #[wasm_bindgen]
-    pub fn set_color(&self, r: f32, g: f32, b: f32) {
-        let gl = &self.gl;
-        let color_location = gl
-            .get_uniform_location(&self.program, "color_multiplier")
-            .expect("Color multiplier uniform not found");
-        gl.uniform3f(Some(&color_location), r, g, b);
-    }
-
-    pub fn draw(&mut self) {
-        let gl = &self.gl;
-
-        gl.clear_color(0.0, 0.0, 0.0, 1.0);
-        gl.clear(GL::COLOR_BUFFER_BIT);
-
-        // Update uniforms
-        let time_location = gl
-            .get_uniform_location(&self.program, "time")
-            .expect("Time uniform not found");
-        let resolution_location = gl
-            .get_uniform_location(&self.program, "resolution")
-            .expect("Resolution uniform not found");
-
-        gl.uniform1f(Some(&time_location), self.time as f32);
-        gl.uniform2f(
-            Some(&resolution_location),
-            self.width as f32,
-            self.height as f32,
-        );
-
-        gl.bind_vertex_array(Some(&self.vertex_array));
-        gl.draw_arrays(GL::POINTS, 0, self.points_count);
-
-        self.time += std::f64::consts::PI / 120.0;
-    }
-
-    pub fn stop(&mut self) {}
-
-    #[wasm_bindgen]
-    pub fn destroy(&mut self) {
-        self.stop();
-    }
-}
+shader_art!(JellyFish, SKETCH);
+layout_bindings!(JellyFish);
@@ -0,0 +1,121 @@
+//! Placement of the point-cloud figures on the canvas, independent of its
+//! size and aspect ratio.
+
+use crate::surface::Viewport;
+use wasm_bindgen::prelude::*;
+
+/// How a figure's design box is fitted into the canvas.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fit {
+    /// Largest size at which the whole design box is visible.
+    Contain,
+    /// Smallest size at which the design box covers the whole canvas.
+    Cover,
+    /// One design pixel per CSS pixel, whatever the canvas size.
+    Fixed,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Layout {
+    /// Size of the box the figure is designed in, centred on its origin.
+    pub design_width: f32,
+    pub design_height: f32,
+    pub fit: Fit,
+    /// Extra zoom applied on top of the fit.
+    pub scale: f32,
+    /// Shift of the figure's centre from the canvas centre in CSS pixels, y down.
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+impl Layout {
+    pub fn new(design_width: f32, design_height: f32) -> Self {
+        Layout {
+            design_width,
+            design_height,
+            fit: Fit::Contain,
+            scale: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        }
+    }
+
+    /// CSS pixels per design pixel.
+    pub fn css_scale(&self, viewport: &Viewport) -> f32 {
+        let sx = viewport.css_width / self.design_width;
+        let sy = viewport.css_height / self.design_height;
+        let fit = match self.fit {
+            Fit::Contain => sx.min(sy),
+            Fit::Cover => sx.max(sy),
+            Fit::Fixed => 1.0,
+        };
+        fit * self.scale
+    }
+
+    /// Model-to-clip transform as `(scale.x, scale.y, offset.x, offset.y)`,
+    /// for model coordinates centred on the design box with y up.
+    pub fn transform(&self, viewport: &Viewport) -> [f32; 4] {
+        let scale = self.css_scale(viewport);
+        [
+            scale * 2.0 / viewport.css_width,
+            scale * 2.0 / viewport.css_height,
+            self.offset_x * 2.0 / viewport.css_width,
+            -self.offset_y * 2.0 / viewport.css_height,
+        ]
+    }
+
+    /// Design pixels from the figure's centre, y down, of the point `x`, `y`
+    /// CSS pixels from the canvas' top left corner.
+    pub fn design_point(&self, viewport: &Viewport, x: f32, y: f32) -> [f32; 2] {
+        let scale = self.css_scale(viewport);
+        [
+            (x - viewport.css_width / 2.0 - self.offset_x) / scale,
+            (y - viewport.css_height / 2.0 - self.offset_y) / scale,
+        ]
+    }
+
+    /// Device pixels per design pixel, for sizing points.
+    pub fn point_scale(&self, viewport: &Viewport) -> f32 {
+        self.css_scale(viewport) * viewport.pixel_ratio
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_the_design_box() {
+        let viewport = Viewport::new(800.0, 400.0, 2.0);
+        let mut layout = Layout::new(400.0, 400.0);
+        assert_eq!(layout.css_scale(&viewport), 1.0);
+        layout.fit = Fit::Cover;
+        assert_eq!(layout.css_scale(&viewport), 2.0);
+        layout.fit = Fit::Fixed;
+        layout.scale = 1.5;
+        assert_eq!(layout.css_scale(&viewport), 1.5);
+        assert_eq!(layout.point_scale(&viewport), 3.0);
+    }
+
+    #[test]
+    fn transform_places_the_centre_at_the_offset() {
+        let viewport = Viewport::new(800.0, 400.0, 1.0);
+        let mut layout = Layout::new(400.0, 400.0);
+        layout.offset_x = 200.0;
+        layout.offset_y = 100.0;
+        // Half the design box across half the canvas height, moved right
+        // and, with clip space y up, down
+        assert_eq!(layout.transform(&viewport), [0.0025, 0.005, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn design_point_inverts_the_placement() {
+        let viewport = Viewport::new(800.0, 400.0, 1.0);
+        let mut layout = Layout::new(400.0, 400.0);
+        layout.scale = 2.0;
+        layout.offset_x = -100.0;
+        assert_eq!(layout.design_point(&viewport, 300.0, 200.0), [0.0, 0.0]);
+        assert_eq!(layout.design_point(&viewport, 340.0, 180.0), [20.0, -10.0]);
+    }
+}
@@ -1,17 +1,40 @@
 #[macro_use]
 mod helpers;
+#[macro_use]
+mod art;
+#[macro_use]
+mod shader_art;
 mod christmas_tree;
+mod clock;
 mod eclipse;
+mod error;
 mod golfed1;
 mod heartbeat;
+mod heartbeat_gpu;
 mod jellyfish;
+mod layout;
+mod mouse;
 mod nudibranch;
 mod planetary_timer;
+mod poisson;
+mod program;
+mod render_loop;
+mod rhythm;
+mod shape;
+mod surface;
+mod worker;
 
+pub use art::*;
 pub use christmas_tree::*;
+pub use clock::TimeMode;
 pub use eclipse::*;
+pub use error::{Error, ShaderStage};
 pub use golfed1::*;
 pub use heartbeat::*;
 pub use jellyfish::*;
+pub use layout::Fit;
 pub use nudibranch::*;
 pub use planetary_timer::*;
+pub use rhythm::RhythmUnit;
+pub use shape::Shape;
+pub use worker::*;
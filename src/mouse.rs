@@ -0,0 +1,82 @@
+//! Pointer state handed to the shader arts as a Shadertoy-style `mouse`
+//! uniform.
+
+use crate::surface::Viewport;
+
+/// Pointer over the canvas, in CSS pixels from its top left corner.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Mouse {
+    /// `None` while the pointer is away from the canvas.
+    position: Option<[f32; 2]>,
+    /// Where the button last went down, `None` before the first press.
+    drag_start: Option<[f32; 2]>,
+    pressed: bool,
+}
+
+impl Mouse {
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.position = Some([x, y]);
+    }
+
+    pub fn press(&mut self, x: f32, y: f32) {
+        self.position = Some([x, y]);
+        self.drag_start = Some([x, y]);
+        self.pressed = true;
+    }
+
+    pub fn release(&mut self, x: f32, y: f32) {
+        self.position = Some([x, y]);
+        self.pressed = false;
+    }
+
+    pub fn leave(&mut self) {
+        self.position = None;
+        self.pressed = false;
+    }
+
+    /// `(x, y, z, w)` in device pixels from the bottom left corner, like
+    /// `gl_FragCoord`. `xy` is the pointer, the canvas centre while it's
+    /// away. `zw` is where the button went down, positive while it's held
+    /// and negated once released, zero before the first press.
+    pub fn uniform(&self, viewport: &Viewport) -> [f32; 4] {
+        let device = |[x, y]: [f32; 2]| {
+            [
+                x * viewport.pixel_ratio,
+                (viewport.css_height - y) * viewport.pixel_ratio,
+            ]
+        };
+        let [x, y] = device(
+            self.position
+                .unwrap_or([viewport.css_width / 2.0, viewport.css_height / 2.0]),
+        );
+        let [z, w] = match self.drag_start.map(device) {
+            Some(start) if self.pressed => start,
+            Some([z, w]) => [-z, -w],
+            None => [0.0, 0.0],
+        };
+        [x, y, z, w]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_is_in_device_pixels_from_the_bottom_left() {
+        let viewport = Viewport::new(200.0, 100.0, 2.0);
+        let mut mouse = Mouse::default();
+        // Centred and without a press until the pointer shows up
+        assert_eq!(mouse.uniform(&viewport), [200.0, 100.0, 0.0, 0.0]);
+
+        mouse.move_to(10.0, 20.0);
+        assert_eq!(mouse.uniform(&viewport), [20.0, 160.0, 0.0, 0.0]);
+        mouse.press(10.0, 20.0);
+        mouse.move_to(50.0, 20.0);
+        assert_eq!(mouse.uniform(&viewport), [100.0, 160.0, 20.0, 160.0]);
+        mouse.release(50.0, 20.0);
+        assert_eq!(mouse.uniform(&viewport), [100.0, 160.0, -20.0, -160.0]);
+        mouse.leave();
+        assert_eq!(mouse.uniform(&viewport), [200.0, 100.0, -20.0, -160.0]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Bridson's Poisson-disk sampling ("Fast Poisson Disk Sampling in Arbitrary
+//! Dimensions", 2007): points no closer than a minimum spacing that still
+//! cover a region evenly, in time linear in the number of points.
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+use std::f32::consts::{PI, SQRT_2};
+
+/// Candidates tried around an active point before it's retired.
+const CANDIDATES: usize = 30;
+
+/// Random points tried as new starts once growth stops, to reach parts of
+/// the region not connected to the seeds.
+const RESTARTS: usize = 30;
+
+/// Fills the region `inside` tells apart, within `bounds` as
+/// `[min_x, min_y, max_x, max_y]`, with points at least `spacing` apart.
+/// Growth starts from `seeds`, which the new points also keep clear of but
+/// which aren't returned.
+pub fn poisson_disk(
+    bounds: [f32; 4],
+    spacing: f32,
+    seeds: &[[f32; 2]],
+    inside: impl Fn(f32, f32) -> bool,
+    rng: &mut SmallRng,
+) -> Vec<[f32; 2]> {
+    let [min_x, min_y, max_x, max_y] = bounds;
+    if spacing <= 0.0 || max_x < min_x || max_y < min_y {
+        return Vec::new();
+    }
+    let mut grid = Grid::new(bounds, spacing);
+    // The grid's last cells reach past the bounds
+    let inside = |x: f32, y: f32| {
+        (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y) && inside(x, y)
+    };
+
+    let mut points: Vec<[f32; 2]> = Vec::new();
+    let mut active = Vec::new();
+    for &seed in seeds {
+        if grid.insert(seed, points.len()) {
+            active.push(points.len());
+            points.push(seed);
+        }
+    }
+    let seed_count = points.len();
+
+    for _ in 0..=RESTARTS {
+        while !active.is_empty() {
+            let slot = rng.gen_range(0..active.len());
+            let [x, y] = points[active[slot]];
+
+            let mut found = false;
+            for _ in 0..CANDIDATES {
+                // Uniform in the annulus between spacing and twice that
+                let angle = rng.gen::<f32>() * 2.0 * PI;
+                let distance = spacing * (1.0 + rng.gen::<f32>());
+                let candidate = [x + angle.cos() * distance, y + angle.sin() * distance];
+                if inside(candidate[0], candidate[1]) && grid.is_clear(candidate, &points) {
+                    grid.insert(candidate, points.len());
+                    active.push(points.len());
+                    points.push(candidate);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                active.swap_remove(slot);
+            }
+        }
+
+        let start = [
+            min_x + (max_x - min_x) * rng.gen::<f32>(),
+            min_y + (max_y - min_y) * rng.gen::<f32>(),
+        ];
+        if inside(start[0], start[1]) && grid.is_clear(start, &points) {
+            grid.insert(start, points.len());
+            active.push(points.len());
+            points.push(start);
+        }
+    }
+
+    points.split_off(seed_count)
+}
+
+/// Background grid with cells small enough to hold one sampled point each,
+/// so only the neighbouring cells need checking.
+struct Grid {
+    min_x: f32,
+    min_y: f32,
+    cell: f32,
+    columns: usize,
+    rows: usize,
+    spacing: f32,
+    /// Indices of the points in each cell. Seeds closer than `spacing` can
+    /// share one, sampled points never do.
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    fn new([min_x, min_y, max_x, max_y]: [f32; 4], spacing: f32) -> Self {
+        let cell = spacing / SQRT_2;
+        let columns = ((max_x - min_x) / cell) as usize + 1;
+        let rows = ((max_y - min_y) / cell) as usize + 1;
+        Grid {
+            min_x,
+            min_y,
+            cell,
+            columns,
+            rows,
+            spacing,
+            cells: vec![Vec::new(); columns * rows],
+        }
+    }
+
+    fn cell_of(&self, [x, y]: [f32; 2]) -> Option<(usize, usize)> {
+        let column = ((x - self.min_x) / self.cell).floor();
+        let row = ((y - self.min_y) / self.cell).floor();
+        if column < 0.0 || row < 0.0 {
+            return None;
+        }
+        let (column, row) = (column as usize, row as usize);
+        (column < self.columns && row < self.rows).then_some((column, row))
+    }
+
+    /// Whether `point` is in the grid and at least `spacing` from every
+    /// point already in it.
+    fn is_clear(&self, point: [f32; 2], points: &[[f32; 2]]) -> bool {
+        let Some((column, row)) = self.cell_of(point) else {
+            return false;
+        };
+        // A cell's diagonal is `spacing`, so two cells either way cover it
+        for r in row.saturating_sub(2)..(row + 3).min(self.rows) {
+            for c in column.saturating_sub(2)..(column + 3).min(self.columns) {
+                for &index in &self.cells[r * self.columns + c] {
+                    let [x, y] = points[index];
+                    if (x - point[0]).hypot(y - point[1]) < self.spacing {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Records point `index` in its cell, if it's in the grid.
+    fn insert(&mut self, point: [f32; 2], index: usize) -> bool {
+        let Some((column, row)) = self.cell_of(point) else {
+            return false;
+        };
+        self.cells[row * self.columns + column].push(index);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn assert_spaced(points: &[[f32; 2]], spacing: f32) {
+        for (i, [ax, ay]) in points.iter().enumerate() {
+            for [bx, by] in &points[i + 1..] {
+                let distance = (ax - bx).hypot(ay - by);
+                assert!(distance >= spacing, "points {} apart", distance);
+            }
+        }
+    }
+
+    #[test]
+    fn points_keep_the_spacing() {
+        for seed in 0..5 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            let points = poisson_disk([0.0, 0.0, 100.0, 100.0], 5.0, &[], |_, _| true, &mut rng);
+            // A square of 100 fits a few hundred points 5 apart
+            assert!(points.len() > 200, "only {} points", points.len());
+            assert!(points
+                .iter()
+                .all(|&[x, y]| (0.0..=100.0).contains(&x) && (0.0..=100.0).contains(&y)));
+            assert_spaced(&points, 5.0);
+        }
+    }
+
+    #[test]
+    fn points_keep_clear_of_the_seeds() {
+        let seeds: Vec<[f32; 2]> = (0..20).map(|i| [i as f32 * 5.0, 50.0]).collect();
+        let inside = |x: f32, y: f32| (x - 50.0).hypot(y - 50.0) < 40.0;
+        let mut rng = SmallRng::seed_from_u64(7);
+        let points = poisson_disk([0.0, 0.0, 100.0, 100.0], 4.0, &seeds, inside, &mut rng);
+        assert!(points.iter().all(|&[x, y]| inside(x, y)));
+        assert_spaced(&[seeds, points].concat(), 4.0);
+    }
+}
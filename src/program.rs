@@ -0,0 +1,140 @@
+//! Linked shader program with its active uniforms and attributes looked up
+//! once, so drawing doesn't cross into JS for every location by name.
+
+use crate::error::Error;
+use crate::helpers::*;
+use std::collections::HashMap;
+use web_sys::{WebGl2RenderingContext as GL, WebGlProgram, WebGlUniformLocation};
+
+pub struct Program {
+    gl: GL,
+    program: WebGlProgram,
+    uniforms: HashMap<String, WebGlUniformLocation>,
+    attributes: HashMap<String, u32>,
+}
+
+impl Program {
+    /// Compiles and links the two stages.
+    pub fn new(gl: &GL, vertex_source: &str, fragment_source: &str) -> Result<Self, Error> {
+        Self::with_feedback(gl, vertex_source, fragment_source, &[])
+    }
+
+    /// Compiles and links the two stages, capturing the vertex shader
+    /// outputs `varyings` with transform feedback.
+    pub fn with_feedback(
+        gl: &GL,
+        vertex_source: &str,
+        fragment_source: &str,
+        varyings: &[&str],
+    ) -> Result<Self, Error> {
+        let vertex_shader = compile_shader(gl, GL::VERTEX_SHADER, vertex_source)?;
+        let fragment_shader = compile_shader(gl, GL::FRAGMENT_SHADER, fragment_source)?;
+        let program = link_program_with_varyings(gl, &vertex_shader, &fragment_shader, varyings)?;
+        Ok(Self::from_linked(gl, program))
+    }
+
+    /// Wraps an already linked program, reading its active uniforms and
+    /// attributes.
+    pub fn from_linked(gl: &GL, program: WebGlProgram) -> Self {
+        let active_count = |kind| {
+            gl.get_program_parameter(&program, kind)
+                .as_f64()
+                .unwrap_or(0.0) as u32
+        };
+
+        let mut uniforms = HashMap::new();
+        for index in 0..active_count(GL::ACTIVE_UNIFORMS) {
+            let Some(info) = gl.get_active_uniform(&program, index) else {
+                continue;
+            };
+            // Arrays are reported as `name[0]`, look them up by `name` too
+            let name = info.name();
+            let name = name.strip_suffix("[0]").unwrap_or(&name);
+            if let Some(location) = gl.get_uniform_location(&program, name) {
+                uniforms.insert(name.to_string(), location);
+            }
+        }
+
+        let mut attributes = HashMap::new();
+        for index in 0..active_count(GL::ACTIVE_ATTRIBUTES) {
+            let Some(info) = gl.get_active_attrib(&program, index) else {
+                continue;
+            };
+            let location = gl.get_attrib_location(&program, &info.name());
+            if location >= 0 {
+                attributes.insert(info.name(), location as u32);
+            }
+        }
+
+        Program {
+            gl: gl.clone(),
+            program,
+            uniforms,
+            attributes,
+        }
+    }
+
+    pub fn use_program(&self) {
+        self.gl.use_program(Some(&self.program));
+    }
+
+    pub fn uniform(&self, name: &str) -> Result<&WebGlUniformLocation, Error> {
+        self.uniforms
+            .get(name)
+            .ok_or_else(|| Error::MissingUniform {
+                name: name.to_string(),
+            })
+    }
+
+    /// Whether the program uses the uniform `name`; the ones the shaders
+    /// never read are optimised away.
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.uniforms.contains_key(name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Result<u32, Error> {
+        self.attributes
+            .get(name)
+            .copied()
+            .ok_or_else(|| Error::MissingAttribute {
+                name: name.to_string(),
+            })
+    }
+
+    // The setters write to the program in use, as `gl.uniform*` does.
+
+    pub fn set_int(&self, name: &str, value: i32) -> Result<(), Error> {
+        self.gl.uniform1i(Some(self.uniform(name)?), value);
+        Ok(())
+    }
+
+    pub fn set_float(&self, name: &str, value: f32) -> Result<(), Error> {
+        self.gl.uniform1f(Some(self.uniform(name)?), value);
+        Ok(())
+    }
+
+    pub fn set_vec2(&self, name: &str, [x, y]: [f32; 2]) -> Result<(), Error> {
+        self.gl.uniform2f(Some(self.uniform(name)?), x, y);
+        Ok(())
+    }
+
+    pub fn set_vec3(&self, name: &str, [x, y, z]: [f32; 3]) -> Result<(), Error> {
+        self.gl.uniform3f(Some(self.uniform(name)?), x, y, z);
+        Ok(())
+    }
+
+    pub fn set_vec4(&self, name: &str, [x, y, z, w]: [f32; 4]) -> Result<(), Error> {
+        self.gl.uniform4f(Some(self.uniform(name)?), x, y, z, w);
+        Ok(())
+    }
+
+    pub fn set_mat4(&self, name: &str, value: &[f32; 16]) -> Result<(), Error> {
+        self.gl
+            .uniform_matrix4fv_with_f32_array(Some(self.uniform(name)?), false, value);
+        Ok(())
+    }
+
+    pub fn delete(&self) {
+        self.gl.delete_program(Some(&self.program));
+    }
+}
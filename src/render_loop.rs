@@ -0,0 +1,156 @@
+//! `requestAnimationFrame` loop owned by Rust, so stopping an art really
+//! cancels its frame callback.
+
+use crate::art::{draw_shared, SharedArt};
+use crate::helpers::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    // Globals rather than `window` methods, so the loop also runs in a worker
+    #[wasm_bindgen(js_name = requestAnimationFrame)]
+    fn request_animation_frame(callback: &Closure<dyn FnMut(f64)>) -> i32;
+
+    #[wasm_bindgen(js_name = cancelAnimationFrame)]
+    fn cancel_animation_frame(id: i32);
+}
+
+#[derive(Default)]
+struct LoopState {
+    /// Holds the only strong reference to the callback while running; taking
+    /// it out breaks the callback -> state cycle.
+    callback: Option<Closure<dyn FnMut(f64)>>,
+    frame_id: Option<i32>,
+    /// Frames per second cap, 0 for the display refresh rate.
+    max_fps: f64,
+    last_frame: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct RenderLoop {
+    state: Rc<RefCell<LoopState>>,
+}
+
+impl RenderLoop {
+    pub fn is_running(&self) -> bool {
+        self.state.borrow().frame_id.is_some()
+    }
+
+    pub fn set_max_fps(&self, max_fps: f64) {
+        let mut state = self.state.borrow_mut();
+        state.max_fps = max_fps.max(0.0);
+        state.last_frame = None;
+    }
+
+    /// Draws `art` on every animation frame until `stop` is called or a
+    /// frame fails to draw.
+    pub fn start(&self, art: SharedArt) {
+        if self.is_running() {
+            return;
+        }
+
+        let state = Rc::downgrade(&self.state);
+        let callback = Closure::<dyn FnMut(f64)>::new(move |timestamp: f64| {
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            let should_draw = {
+                let mut state = state.borrow_mut();
+                state.frame_id = None;
+                state.should_draw(timestamp)
+            };
+
+            // Not borrowing the state while drawing, as the art's listeners
+            // may stop the loop
+            if should_draw {
+                if let Err(e) = draw_shared(&art, Some(timestamp)) {
+                    // Nobody is there to catch it, and every frame would fail the same way
+                    console_error!("Stopping render loop: {}", e);
+                    return;
+                }
+            }
+
+            let mut state = state.borrow_mut();
+            if let Some(callback) = &state.callback {
+                state.frame_id = Some(request_animation_frame(callback));
+            }
+        });
+
+        let mut state = self.state.borrow_mut();
+        state.frame_id = Some(request_animation_frame(&callback));
+        state.callback = Some(callback);
+        state.last_frame = None;
+    }
+
+    pub fn stop(&self) {
+        let mut state = self.state.borrow_mut();
+        if let Some(frame_id) = state.frame_id.take() {
+            cancel_animation_frame(frame_id);
+        }
+        state.callback = None;
+    }
+}
+
+impl Drop for RenderLoop {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// How early a frame may arrive and still count, as rAF timestamps jitter.
+const FRAME_TOLERANCE_MS: f64 = 1.0;
+
+impl LoopState {
+    /// Applies the fps cap, keeping frames on a fixed grid so the rate doesn't
+    /// drift below the cap when the display rate isn't a multiple of it.
+    fn should_draw(&mut self, timestamp: f64) -> bool {
+        if self.max_fps <= 0.0 {
+            return true;
+        }
+
+        let interval = 1000.0 / self.max_fps;
+        let Some(last) = self.last_frame else {
+            self.last_frame = Some(timestamp);
+            return true;
+        };
+
+        let elapsed = timestamp - last + FRAME_TOLERANCE_MS;
+        if elapsed < interval {
+            return false;
+        }
+        self.last_frame = Some(last + (elapsed / interval).floor() * interval);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn(max_fps: f64, timestamps: impl IntoIterator<Item = f64>) -> Vec<f64> {
+        let mut state = LoopState {
+            max_fps,
+            ..LoopState::default()
+        };
+        timestamps
+            .into_iter()
+            .filter(|&timestamp| state.should_draw(timestamp))
+            .collect()
+    }
+
+    #[test]
+    fn uncapped_draws_every_frame() {
+        assert_eq!(drawn(0.0, [0.0, 7.0, 16.0]), [0.0, 7.0, 16.0]);
+    }
+
+    #[test]
+    fn cap_keeps_frames_on_a_grid() {
+        // 144 Hz display capped at 60 fps: frames every 6.94 ms
+        let frames = (0..144).map(|i| i as f64 * 1000.0 / 144.0);
+        assert_eq!(drawn(60.0, frames).len(), 60);
+        // Jitter within the tolerance still counts
+        assert_eq!(drawn(30.0, [0.0, 33.0, 66.5]), [0.0, 33.0, 66.5]);
+    }
+}
@@ -0,0 +1,296 @@
+//! Beat timeline from recorded heart data, RR intervals or heart rate
+//! samples, for Heartbeat to beat to instead of a constant rate.
+
+use crate::error::Error;
+use js_sys::{Array, JSON};
+use wasm_bindgen::prelude::*;
+
+/// What the numbers of a recording are.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RhythmUnit {
+    /// Time between two beats in milliseconds.
+    RrMilliseconds,
+    /// Time between two beats in seconds.
+    RrSeconds,
+    /// Beats per minute, each sample standing for one beat.
+    Bpm,
+}
+
+impl RhythmUnit {
+    /// Milliseconds until the next beat for a `value` in this unit.
+    fn interval(self, value: f64) -> f64 {
+        match self {
+            RhythmUnit::RrMilliseconds => value,
+            RhythmUnit::RrSeconds => value * 1000.0,
+            RhythmUnit::Bpm => 60_000.0 / value,
+        }
+    }
+}
+
+/// Shortest time between two beats, well above any real heart's; shorter
+/// intervals would have a looping recording beat countless times a frame.
+const MIN_INTERVAL_MS: f64 = 100.0;
+
+/// Playback speed of the recording from animation time `time` on, when
+/// `played` milliseconds of it had played.
+#[derive(Clone, Copy)]
+struct SpeedChange {
+    time: f64,
+    played: f64,
+    speed: f64,
+}
+
+pub struct Rhythm {
+    /// Time of each beat from the start of the recording, in milliseconds.
+    beat_times: Vec<f64>,
+    /// Animation time the recording started playing at.
+    pub began: f64,
+    /// Every speed change in time order, the first at `began`. Kept rather
+    /// than folded into the latest one, so replaying from before a change
+    /// plays the beats as they were.
+    speeds: Vec<SpeedChange>,
+    /// Plays the recording again from the top once it runs out.
+    pub looping: bool,
+}
+
+impl Rhythm {
+    /// Empty recording starting at animation time `start`, to push samples to.
+    pub fn new(start: f64) -> Self {
+        Rhythm {
+            beat_times: Vec::new(),
+            began: start,
+            speeds: vec![SpeedChange {
+                time: start,
+                played: 0.0,
+                speed: 1.0,
+            }],
+            looping: false,
+        }
+    }
+
+    /// Reads one sample per line, taking the last number on the line so
+    /// both `800` and `12:00:01,800` work. Lines without one, like a
+    /// header, are skipped.
+    pub fn from_csv(text: &str, unit: RhythmUnit, start: f64) -> Result<Self, Error> {
+        let samples = text.lines().filter_map(|line| {
+            line.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+                .rev()
+                .find_map(|field| field.trim().parse::<f64>().ok())
+        });
+        Self::from_samples(samples, unit, start)
+    }
+
+    /// Reads a JSON array of numbers.
+    pub fn from_json(text: &str, unit: RhythmUnit, start: f64) -> Result<Self, Error> {
+        let value = JSON::parse(text).map_err(|_| invalid_rhythm("not valid JSON".to_string()))?;
+        if !Array::is_array(&value) {
+            return Err(invalid_rhythm("expected an array of numbers".to_string()));
+        }
+        let samples = Array::from(&value)
+            .iter()
+            .map(|sample| sample.as_f64().unwrap_or(f64::NAN))
+            .collect::<Vec<_>>();
+        Self::from_samples(samples, unit, start)
+    }
+
+    fn from_samples(
+        samples: impl IntoIterator<Item = f64>,
+        unit: RhythmUnit,
+        start: f64,
+    ) -> Result<Self, Error> {
+        let mut rhythm = Rhythm::new(start);
+        for sample in samples {
+            rhythm.push(sample, unit)?;
+        }
+        if rhythm.beat_times.is_empty() {
+            return Err(invalid_rhythm("no samples".to_string()));
+        }
+        Ok(rhythm)
+    }
+
+    /// Adds a beat `value` after the last one, at least `MIN_INTERVAL_MS`
+    /// later.
+    pub fn push(&mut self, value: f64, unit: RhythmUnit) -> Result<(), Error> {
+        let interval = unit.interval(value);
+        if !(interval.is_finite() && interval >= MIN_INTERVAL_MS) {
+            return Err(invalid_rhythm(format!(
+                "sample {} is not a beat interval of at least {} ms",
+                self.beat_times.len() + 1,
+                MIN_INTERVAL_MS
+            )));
+        }
+        self.beat_times.push(self.duration() + interval);
+        Ok(())
+    }
+
+    /// Length of the recording in milliseconds, up to its last beat.
+    fn duration(&self) -> f64 {
+        self.beat_times.last().copied().unwrap_or(0.0)
+    }
+
+    /// Plays on at `speed` from animation time `now`, keeping the part of
+    /// the recording already played as it was. Changes made later than
+    /// `now`, before seeking back, are dropped.
+    pub fn set_speed(&mut self, now: f64, speed: f64) {
+        let speed = speed.max(0.0);
+        if now <= self.began {
+            self.speeds.truncate(1);
+            self.speeds[0].speed = speed;
+            return;
+        }
+        self.speeds.retain(|change| change.time < now);
+        let last = self.speeds[self.speeds.len() - 1];
+        self.speeds.push(SpeedChange {
+            time: now,
+            played: last.played + (now - last.time) * last.speed,
+            speed,
+        });
+    }
+
+    /// Animation time of beat `index`, `None` past the end of a recording
+    /// that doesn't loop or doesn't play.
+    pub fn beat_time(&self, index: usize) -> Option<f64> {
+        let count = self.beat_times.len();
+        if count == 0 || (!self.looping && index >= count) {
+            return None;
+        }
+        let lap = (index / count) as f64;
+        let played = lap * self.duration() + self.beat_times[index % count];
+        // The last change that had played no further than the beat; a pause
+        // and the change resuming it played as far, and the resume counts
+        let change = self
+            .speeds
+            .iter()
+            .rev()
+            .find(|change| change.played <= played)?;
+        if change.speed <= 0.0 {
+            return None;
+        }
+        Some(change.time + (played - change.played) / change.speed)
+    }
+
+    /// Index of the first beat from `from` on that comes after `time`.
+    pub fn next_after(&self, time: f64, from: usize) -> usize {
+        let mut index = from;
+        while self.beat_time(index).is_some_and(|beat| beat <= time) {
+            index += 1;
+        }
+        index
+    }
+}
+
+fn invalid_rhythm(reason: String) -> Error {
+    Error::InvalidRhythm { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beat_times(rhythm: &Rhythm, count: usize) -> Vec<Option<f64>> {
+        (0..count).map(|index| rhythm.beat_time(index)).collect()
+    }
+
+    #[test]
+    fn csv_takes_the_last_number_of_each_line() {
+        let csv = "time,rr\n12:00:01,800\n12:00:02; 1000\n\n1200\n";
+        let rhythm = Rhythm::from_csv(csv, RhythmUnit::RrMilliseconds, 500.0).unwrap();
+        assert_eq!(
+            beat_times(&rhythm, 4),
+            vec![Some(1300.0), Some(2300.0), Some(3500.0), None]
+        );
+    }
+
+    #[test]
+    fn csv_units() {
+        let seconds = Rhythm::from_csv("0.5\n0.25", RhythmUnit::RrSeconds, 0.0).unwrap();
+        assert_eq!(beat_times(&seconds, 2), vec![Some(500.0), Some(750.0)]);
+        let bpm = Rhythm::from_csv("60\n120", RhythmUnit::Bpm, 0.0).unwrap();
+        assert_eq!(beat_times(&bpm, 2), vec![Some(1000.0), Some(1500.0)]);
+    }
+
+    #[test]
+    fn csv_rejects_bad_samples() {
+        assert!(matches!(
+            Rhythm::from_csv("800\n0\n", RhythmUnit::RrMilliseconds, 0.0),
+            Err(Error::InvalidRhythm { .. })
+        ));
+        assert!(matches!(
+            Rhythm::from_csv("rr\n", RhythmUnit::RrMilliseconds, 0.0),
+            Err(Error::InvalidRhythm { .. })
+        ));
+        assert!(matches!(
+            Rhythm::from_csv("800\n50\n", RhythmUnit::RrMilliseconds, 0.0),
+            Err(Error::InvalidRhythm { .. })
+        ));
+        assert!(matches!(
+            Rhythm::from_csv("1e9", RhythmUnit::Bpm, 0.0),
+            Err(Error::InvalidRhythm { .. })
+        ));
+    }
+
+    #[test]
+    fn looping_plays_the_recording_again() {
+        let mut rhythm = Rhythm::from_csv("800\n1000", RhythmUnit::RrMilliseconds, 0.0).unwrap();
+        rhythm.looping = true;
+        assert_eq!(
+            beat_times(&rhythm, 5),
+            vec![
+                Some(800.0),
+                Some(1800.0),
+                Some(2600.0),
+                Some(3600.0),
+                Some(4400.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn next_after_skips_the_beats_passed() {
+        let rhythm = Rhythm::from_csv("1000\n1000\n1000", RhythmUnit::RrMilliseconds, 0.0).unwrap();
+        assert_eq!(rhythm.next_after(0.0, 0), 0);
+        assert_eq!(rhythm.next_after(1000.0, 0), 1);
+        assert_eq!(rhythm.next_after(2500.0, 1), 2);
+        assert_eq!(rhythm.next_after(5000.0, 0), 3);
+        assert_eq!(rhythm.beat_time(3), None);
+    }
+
+    #[test]
+    fn speed_change_keeps_the_playhead() {
+        let csv = "1000\n1000\n1000\n1000\n1000\n1000";
+        let mut rhythm = Rhythm::from_csv(csv, RhythmUnit::RrMilliseconds, 0.0).unwrap();
+        rhythm.set_speed(3500.0, 2.0);
+        assert_eq!(rhythm.next_after(3500.0, 3), 3);
+        assert_eq!(
+            beat_times(&rhythm, 7)[3..],
+            [Some(3750.0), Some(4250.0), Some(4750.0), None]
+        );
+
+        // Paused, then playing on from where it stopped
+        rhythm.set_speed(4000.0, 0.0);
+        assert_eq!(rhythm.beat_time(4), None);
+        rhythm.set_speed(10_000.0, 1.0);
+        assert_eq!(rhythm.beat_time(4), Some(10_500.0));
+    }
+
+    #[test]
+    fn speed_changes_replay_as_they_were() {
+        let csv = "1000\n1000\n1000\n1000\n1000\n1000";
+        let mut rhythm = Rhythm::from_csv(csv, RhythmUnit::RrMilliseconds, 0.0).unwrap();
+        rhythm.set_speed(3500.0, 2.0);
+        // Beats before the change keep their times for a replay from 0
+        assert_eq!(
+            beat_times(&rhythm, 4),
+            [Some(1000.0), Some(2000.0), Some(3000.0), Some(3750.0)]
+        );
+        assert_eq!(rhythm.next_after(2500.0, 0), 2);
+
+        // A change after seeking back replaces the ones that came later
+        rhythm.set_speed(1500.0, 0.5);
+        assert_eq!(
+            beat_times(&rhythm, 3),
+            [Some(1000.0), Some(2500.0), Some(4500.0)]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! What the fragment and point-cloud arts have in common: the canvas, its
+//! context, the clock, pointer and layout, and the uniforms every frame
+//! uploads. Each art only supplies a `Sketch` of its shaders and geometry.
+
+use crate::art::Art;
+use crate::clock::Clock;
+use crate::error::Error;
+use crate::helpers::*;
+use crate::layout::Layout;
+use crate::mouse::Mouse;
+use crate::program::Program;
+use crate::surface::{ContextWatch, Surface, Viewport};
+use web_sys::WebGl2RenderingContext as GL;
+
+/// Vertex shader of the arts drawn entirely by their fragment shader.
+const FULL_SCREEN_VERTEX_SHADER: &str = r#"#version 300 es
+    in vec2 position;
+
+    void main() {
+        gl_Position = vec4(position, 0.0, 1.0);
+    }
+"#;
+
+/// Columns of the grid the point-cloud arts lay their points out on.
+const GRID_COLUMNS: i32 = 200;
+
+/// Side of the square design box of the point-cloud arts.
+const DESIGN_SIZE: f32 = 400.0;
+
+/// What sets one shader art apart from the others.
+pub struct Sketch {
+    /// Animation time per 60 Hz frame.
+    pub step: f64,
+    pub geometry: Geometry,
+    /// Reads `time`, and `resolution`, `mouse` and `color_multiplier` as it
+    /// needs.
+    pub fragment_shader: &'static str,
+}
+
+pub enum Geometry {
+    /// Two triangles covering the canvas.
+    FullScreen,
+    /// `count` points on a grid `GRID_COLUMNS` wide, their column and row in
+    /// `position`, which `vertex_shader` moves about a 400x400 design box
+    /// placed by `transform` and, if it reads it, sized by `point_size`.
+    Points {
+        count: i32,
+        vertex_shader: &'static str,
+    },
+}
+
+impl Sketch {
+    fn vertex_shader(&self) -> &'static str {
+        match self.geometry {
+            Geometry::FullScreen => FULL_SCREEN_VERTEX_SHADER,
+            Geometry::Points { vertex_shader, .. } => vertex_shader,
+        }
+    }
+
+    fn vertices(&self) -> Vec<f32> {
+        match self.geometry {
+            Geometry::FullScreen => vec![
+                -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0,
+            ],
+            Geometry::Points { count, .. } => (0..count)
+                .flat_map(|i| [(i % GRID_COLUMNS) as f32, (i / GRID_COLUMNS) as f32])
+                .collect(),
+        }
+    }
+
+    fn draw(&self, gl: &GL) {
+        match self.geometry {
+            Geometry::FullScreen => gl.draw_arrays(GL::TRIANGLES, 0, 6),
+            Geometry::Points { count, .. } => gl.draw_arrays(GL::POINTS, 0, count),
+        }
+    }
+
+    fn create_resources(&self, gl: &GL) -> Result<GpuResources, Error> {
+        let program = Program::new(gl, self.vertex_shader(), self.fragment_shader)?;
+        program.use_program();
+
+        let vertex_array = gl.create_vertex_array().ok_or(Error::CreateObject {
+            object: "vertex array",
+        })?;
+        gl.bind_vertex_array(Some(&vertex_array));
+
+        let buffer = gl
+            .create_buffer()
+            .ok_or(Error::CreateObject { object: "buffer" })?;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+
+        let vertices = self.vertices();
+        unsafe {
+            let vert_array = js_sys::Float32Array::view(&vertices);
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &vert_array, GL::STATIC_DRAW);
+        }
+
+        let position_attribute_location = program.attribute("position")?;
+        gl.enable_vertex_attrib_array(position_attribute_location);
+        gl.vertex_attrib_pointer_with_i32(position_attribute_location, 2, GL::FLOAT, false, 8, 0);
+
+        Ok(GpuResources {
+            program,
+            vertex_array,
+            buffer,
+        })
+    }
+}
+
+/// A `Sketch` drawn on a surface, with the state every shader art keeps.
+pub struct ShaderArt {
+    sketch: &'static Sketch,
+    surface: Surface,
+    context: ContextWatch,
+    gl: GL,
+    resources: Option<GpuResources>,
+    /// Last colour set, re-applied when a lost context is restored.
+    color: [f32; 3],
+    clock: Clock,
+    viewport: Viewport,
+    /// Placement of the figure, for point-cloud sketches.
+    layout: Option<Layout>,
+    mouse: Mouse,
+}
+
+impl ShaderArt {
+    pub fn new(
+        sketch: &'static Sketch,
+        surface: Surface,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        let viewport = Viewport::new(width as f32, height as f32, 1.0);
+        let gl = surface.webgl2_context(&viewport)?;
+        let context = ContextWatch::new(&surface)?;
+        let resources = sketch.create_resources(&gl)?;
+        let layout = match sketch.geometry {
+            Geometry::FullScreen => None,
+            Geometry::Points { .. } => Some(Layout::new(DESIGN_SIZE, DESIGN_SIZE)),
+        };
+
+        Ok(ShaderArt {
+            sketch,
+            surface,
+            context,
+            gl,
+            resources: Some(resources),
+            color: [0.0; 3],
+            clock: Clock::new(sketch.step),
+            viewport,
+            layout,
+            mouse: Mouse::default(),
+        })
+    }
+
+    /// Rebuilds the GPU objects on a restored context, keeping the current
+    /// time and colour.
+    fn restore(&mut self) {
+        self.resources = self
+            .sketch
+            .create_resources(&self.gl)
+            .map_err(|e| console_error!("Failed to restore WebGL resources: {}", e))
+            .ok();
+        self.surface.resize(&self.gl, &self.viewport);
+        let [r, g, b] = self.color;
+        self.set_color(r, g, b);
+        self.clock.rebase();
+    }
+}
+
+impl Art for ShaderArt {
+    fn resize(&mut self, width: f32, height: f32, pixel_ratio: f32) {
+        self.viewport = Viewport::new(width, height, pixel_ratio);
+        self.surface.resize(&self.gl, &self.viewport);
+    }
+
+    fn layout_mut(&mut self) -> Option<&mut Layout> {
+        self.layout.as_mut()
+    }
+
+    fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.color = [r, g, b];
+        if self.context.is_lost() {
+            return;
+        }
+        let Some(resources) = &self.resources else {
+            return;
+        };
+        let program = &resources.program;
+        if !program.has_uniform("color_multiplier") {
+            return;
+        }
+        program.use_program();
+        if let Err(e) = program.set_vec3("color_multiplier", [r, g, b]) {
+            console_error!("{}", e);
+        }
+    }
+
+    fn mouse_mut(&mut self) -> Option<&mut Mouse> {
+        Some(&mut self.mouse)
+    }
+
+    fn draw(&mut self, timestamp: Option<f64>) -> Result<(), Error> {
+        if self.context.take_restored() {
+            self.restore();
+        }
+        if self.context.is_lost() {
+            return Ok(());
+        }
+        let Some(resources) = &self.resources else {
+            return Ok(());
+        };
+        let time = self.clock.tick(timestamp);
+        let gl = &self.gl;
+
+        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        gl.clear(GL::COLOR_BUFFER_BIT);
+
+        let program = &resources.program;
+        program.use_program();
+        program.set_float("time", time as f32)?;
+        if program.has_uniform("resolution") {
+            program.set_vec2(
+                "resolution",
+                [self.viewport.width() as f32, self.viewport.height() as f32],
+            )?;
+        }
+        if program.has_uniform("mouse") {
+            program.set_vec4("mouse", self.mouse.uniform(&self.viewport))?;
+        }
+        if let Some(layout) = &self.layout {
+            program.set_vec4("transform", layout.transform(&self.viewport))?;
+            if program.has_uniform("point_size") {
+                program.set_float("point_size", layout.point_scale(&self.viewport))?;
+            }
+        }
+
+        gl.bind_vertex_array(Some(&resources.vertex_array));
+        self.sketch.draw(gl);
+        Ok(())
+    }
+
+    fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    fn release(&mut self) {
+        if let Some(resources) = self.resources.take() {
+            resources.delete(&self.gl);
+        }
+    }
+}
+
+impl Drop for ShaderArt {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Declares the `#[wasm_bindgen]` art class `$art` drawing the `Sketch`
+/// `$sketch`.
+macro_rules! shader_art {
+    ($art:ident, $sketch:expr) => {
+        art_class!(
+            $art($crate::shader_art::ShaderArt),
+            |surface, width, height| $crate::shader_art::ShaderArt::new(
+                &$sketch, surface, width, height
+            )
+        );
+    };
+}
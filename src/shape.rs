@@ -0,0 +1,449 @@
+//! Closed shapes for the Heartbeat particles to form, held as polygons in
+//! design pixels around the origin with y down. Outline particles are spread
+//! along the edges and fill particles placed inside by the even-odd rule.
+
+use crate::error::Error;
+use rand::rngs::SmallRng;
+use rand::Rng;
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+
+/// Size of the longer side of an SVG shape once fitted, about the heart's.
+const SHAPE_SIZE: f32 = 256.0;
+
+/// Straight segments a Bézier curve is flattened into.
+const CURVE_SEGMENTS: usize = 16;
+
+/// Edges of the heart polygon.
+const HEART_SEGMENTS: usize = 256;
+
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Shape {
+    /// Closed polygons, the last point joining back to the first.
+    contours: Vec<Vec<[f32; 2]>>,
+}
+
+#[wasm_bindgen]
+impl Shape {
+    /// The parametric heart the animation has always drawn.
+    pub fn heart() -> Shape {
+        let contour = (0..HEART_SEGMENTS)
+            .map(|i| {
+                let t = i as f32 * 2.0 * PI / HEART_SEGMENTS as f32;
+                [heart_x(t) * 8.0, -heart_y(t) * 8.0]
+            })
+            .collect();
+        Shape {
+            contours: vec![contour],
+        }
+    }
+
+    /// Star with `points` tips, its inner corners at `inner_ratio` of the
+    /// tips' radius, pointing up.
+    pub fn star(points: u32, inner_ratio: f32) -> Shape {
+        let points = points.max(2);
+        let radius = SHAPE_SIZE / 2.0;
+        let contour = (0..points * 2)
+            .map(|i| {
+                let angle = i as f32 * PI / points as f32 - PI / 2.0;
+                let r = if i % 2 == 0 {
+                    radius
+                } else {
+                    radius * inner_ratio
+                };
+                [angle.cos() * r, angle.sin() * r]
+            })
+            .collect();
+        Shape {
+            contours: vec![contour],
+        }
+    }
+
+    /// Parses SVG path data made of `M`, `L`, `C`, `Q` and `Z` commands,
+    /// absolute or relative, and fits it to the heart's size. Every subpath
+    /// is closed, and holes follow the even-odd rule.
+    pub fn from_svg_path(d: &str) -> Result<Shape, Error> {
+        let mut shape = Shape {
+            contours: PathParser::new(d).parse()?,
+        };
+        shape.fit(SHAPE_SIZE)?;
+        Ok(shape)
+    }
+}
+
+impl Shape {
+    /// Scales and moves the shape so its bounding box is centred on the
+    /// origin with its longer side `size` long.
+    fn fit(&mut self, size: f32) -> Result<(), Error> {
+        let [min_x, min_y, max_x, max_y] = self.bounds();
+        let extent = (max_x - min_x).max(max_y - min_y);
+        if self.contours.is_empty() || extent <= 0.0 {
+            return Err(invalid_path("the path encloses no area"));
+        }
+        let scale = size / extent;
+        let (cx, cy) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+        for point in self.contours.iter_mut().flatten() {
+            *point = [(point[0] - cx) * scale, (point[1] - cy) * scale];
+        }
+        Ok(())
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` of all the contours.
+    pub fn bounds(&self) -> [f32; 4] {
+        self.contours.iter().flatten().fold(
+            [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+            |[min_x, min_y, max_x, max_y], &[x, y]| {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            },
+        )
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ([f32; 2], [f32; 2])> + '_ {
+        self.contours.iter().flat_map(|contour| {
+            contour
+                .iter()
+                .zip(contour.iter().cycle().skip(1))
+                .map(|(&a, &b)| (a, b))
+        })
+    }
+
+    /// `count` points evenly spaced along all the edges.
+    pub fn outline(&self, count: usize) -> Vec<[f32; 2]> {
+        let length = |([ax, ay], [bx, by]): ([f32; 2], [f32; 2])| (bx - ax).hypot(by - ay);
+        let perimeter: f32 = self.edges().map(length).sum();
+        let mut points = Vec::with_capacity(count);
+        if count == 0 || perimeter <= 0.0 {
+            return points;
+        }
+
+        let step = perimeter / count as f32;
+        let mut next = 0.0;
+        let mut travelled = 0.0;
+        for (a, b) in self.edges() {
+            let edge = length((a, b));
+            while next < travelled + edge && points.len() < count {
+                let f = (next - travelled) / edge;
+                points.push([a[0] + (b[0] - a[0]) * f, a[1] + (b[1] - a[1]) * f]);
+                next += step;
+            }
+            travelled += edge;
+        }
+        points
+    }
+
+    /// Even-odd inside test: a ray from the point crosses the edges an odd
+    /// number of times.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let mut inside = false;
+        for ([ax, ay], [bx, by]) in self.edges() {
+            if (ay > y) != (by > y) && x < (bx - ax) * (y - ay) / (by - ay) + ax {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Uniformly random point of the bounding box, not necessarily inside.
+    pub fn random_point(&self, rng: &mut SmallRng) -> [f32; 2] {
+        let [min_x, min_y, max_x, max_y] = self.bounds();
+        [
+            min_x + (max_x - min_x) * rng.gen::<f32>(),
+            min_y + (max_y - min_y) * rng.gen::<f32>(),
+        ]
+    }
+}
+
+fn heart_x(t: f32) -> f32 {
+    16.0 * (t.sin().powi(3))
+}
+
+fn heart_y(t: f32) -> f32 {
+    13.0 * t.cos() - 5.0 * (2.0 * t).cos() - 2.0 * (3.0 * t).cos() - (4.0 * t).cos()
+}
+
+fn invalid_path(reason: &str) -> Error {
+    Error::InvalidPath {
+        reason: reason.to_string(),
+    }
+}
+
+/// Reads SVG path data into flattened contours.
+struct PathParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    contours: Vec<Vec<[f32; 2]>>,
+    contour: Vec<[f32; 2]>,
+    current: [f32; 2],
+    /// Start of the current subpath, where `Z` goes back to.
+    start: [f32; 2],
+}
+
+impl<'a> PathParser<'a> {
+    fn new(d: &'a str) -> Self {
+        PathParser {
+            data: d.as_bytes(),
+            pos: 0,
+            contours: Vec::new(),
+            contour: Vec::new(),
+            current: [0.0, 0.0],
+            start: [0.0, 0.0],
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Vec<[f32; 2]>>, Error> {
+        let mut command = None;
+        loop {
+            self.skip_separators();
+            let Some(&next) = self.data.get(self.pos) else {
+                break;
+            };
+            if next.is_ascii_alphabetic() {
+                command = Some(next);
+                self.pos += 1;
+            } else if command.is_none() {
+                return Err(invalid_path(&format!(
+                    "expected a command at offset {}",
+                    self.pos
+                )));
+            }
+
+            // Numbers after a command repeat it, `M` repeating as `L`
+            match command {
+                Some(c @ (b'M' | b'm')) => {
+                    let point = self.point(c == b'm')?;
+                    self.close();
+                    self.start = point;
+                    self.current = point;
+                    self.contour.push(point);
+                    command = Some(if c == b'M' { b'L' } else { b'l' });
+                }
+                Some(c @ (b'L' | b'l')) => {
+                    let point = self.point(c == b'l')?;
+                    self.line_to(point);
+                }
+                Some(c @ (b'C' | b'c')) => {
+                    let c1 = self.point(c == b'c')?;
+                    let c2 = self.point(c == b'c')?;
+                    let end = self.point(c == b'c')?;
+                    let p0 = self.current;
+                    self.curve_to(|t| {
+                        let s = 1.0 - t;
+                        let w = [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t];
+                        [
+                            w[0] * p0[0] + w[1] * c1[0] + w[2] * c2[0] + w[3] * end[0],
+                            w[0] * p0[1] + w[1] * c1[1] + w[2] * c2[1] + w[3] * end[1],
+                        ]
+                    });
+                }
+                Some(c @ (b'Q' | b'q')) => {
+                    let c1 = self.point(c == b'q')?;
+                    let end = self.point(c == b'q')?;
+                    let p0 = self.current;
+                    self.curve_to(|t| {
+                        let s = 1.0 - t;
+                        let w = [s * s, 2.0 * s * t, t * t];
+                        [
+                            w[0] * p0[0] + w[1] * c1[0] + w[2] * end[0],
+                            w[0] * p0[1] + w[1] * c1[1] + w[2] * end[1],
+                        ]
+                    });
+                }
+                Some(b'Z' | b'z') => {
+                    self.close();
+                    self.current = self.start;
+                    // Z takes no numbers, the next command must be explicit
+                    command = None;
+                }
+                Some(other) => {
+                    return Err(invalid_path(&format!(
+                        "unsupported command `{}`",
+                        other as char
+                    )))
+                }
+                None => unreachable!(),
+            }
+        }
+        self.close();
+        Ok(self.contours)
+    }
+
+    fn line_to(&mut self, point: [f32; 2]) {
+        // Drawing on after a `Z` starts a new subpath where the last ended
+        if self.contour.is_empty() {
+            self.contour.push(self.current);
+        }
+        self.contour.push(point);
+        self.current = point;
+    }
+
+    fn curve_to(&mut self, curve: impl Fn(f32) -> [f32; 2]) {
+        for i in 1..=CURVE_SEGMENTS {
+            self.line_to(curve(i as f32 / CURVE_SEGMENTS as f32));
+        }
+    }
+
+    /// Ends the current subpath, keeping it if it has an area.
+    fn close(&mut self) {
+        let mut contour = std::mem::take(&mut self.contour);
+        if contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+        if contour.len() >= 3 {
+            self.contours.push(contour);
+        }
+    }
+
+    fn point(&mut self, relative: bool) -> Result<[f32; 2], Error> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(if relative {
+            [self.current[0] + x, self.current[1] + y]
+        } else {
+            [x, y]
+        })
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.data.get(self.pos) {
+            if !(c.is_ascii_whitespace() || *c == b',') {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Reads a number like `-1.5e3`, which may follow the previous one with
+    /// no separator, as in `10-5` or `.5.5`.
+    fn number(&mut self) -> Result<f32, Error> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            while parser.data.get(parser.pos).is_some_and(u8::is_ascii_digit) {
+                parser.pos += 1;
+            }
+        };
+
+        if matches!(self.data.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        digits(self);
+        if self.data.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            digits(self);
+        }
+        if matches!(self.data.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.data.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            digits(self);
+        }
+
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| invalid_path(&format!("expected a number at offset {}", start)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [[f32; 2]; 4] = [
+        [-128.0, -128.0],
+        [128.0, -128.0],
+        [128.0, 128.0],
+        [-128.0, 128.0],
+    ];
+
+    fn contours(d: &str) -> Vec<Vec<[f32; 2]>> {
+        PathParser::new(d).parse().unwrap()
+    }
+
+    #[test]
+    fn lines_absolute_and_relative() {
+        for d in [
+            "M 0 0 L 10 0 L 10 10 L 0 10 Z",
+            "m 0 0 l 10 0 l 0 10 l -10 0 z",
+            "M 0 0 L 10 0 L 10 10 L 0 10 L 0 0",
+        ] {
+            assert_eq!(
+                Shape::from_svg_path(d).unwrap().contours,
+                vec![SQUARE.to_vec()]
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_after_moveto_are_lines() {
+        for d in ["M 0 0 10 0 10 10 0 10 Z", "m0,0 10,0 0,10 -10,0z"] {
+            assert_eq!(
+                Shape::from_svg_path(d).unwrap().contours,
+                vec![SQUARE.to_vec()]
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_without_separators() {
+        assert_eq!(
+            contours("M10-5L.5.5 1e1 1Z"),
+            vec![vec![[10.0, -5.0], [0.5, 0.5], [10.0, 1.0]]]
+        );
+    }
+
+    #[test]
+    fn curves_absolute_and_relative() {
+        for d in ["M 0 0 C 0 10 10 10 10 0 Z", "M 0 0 c 0 10 10 10 10 0 z"] {
+            let contour = &contours(d)[0];
+            assert_eq!(contour.len(), 1 + CURVE_SEGMENTS);
+            assert_eq!(contour[CURVE_SEGMENTS / 2], [5.0, 7.5]);
+            assert_eq!(contour[CURVE_SEGMENTS], [10.0, 0.0]);
+        }
+        for d in ["M 0 0 Q 5 10 10 0 Z", "M 0 0 q 5 10 10 0 z"] {
+            let contour = &contours(d)[0];
+            assert_eq!(contour.len(), 1 + CURVE_SEGMENTS);
+            assert_eq!(contour[CURVE_SEGMENTS / 2], [5.0, 5.0]);
+            assert_eq!(contour[CURVE_SEGMENTS], [10.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn relative_moveto_after_close() {
+        assert_eq!(
+            contours("M 0 0 L 1 0 L 1 1 Z m 5 0 l 1 0 l 0 1 z"),
+            vec![
+                vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]],
+                vec![[5.0, 0.0], [6.0, 0.0], [6.0, 1.0]],
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_command() {
+        match Shape::from_svg_path("M 0 0 H 10 V 10 Z") {
+            Err(Error::InvalidPath { reason }) => assert_eq!(reason, "unsupported command `H`"),
+            other => panic!("expected InvalidPath, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn no_area() {
+        assert!(matches!(
+            Shape::from_svg_path("M 0 0 L 10 0"),
+            Err(Error::InvalidPath { .. })
+        ));
+    }
+
+    #[test]
+    fn hole_by_even_odd_rule() {
+        let shape = Shape::from_svg_path("M 0 0 L 10 0 L 10 10 L 0 10 Z M 3 3 L 7 3 L 7 7 L 3 7 Z")
+            .unwrap();
+        assert!(!shape.contains(0.0, 0.0));
+        assert!(shape.contains(100.0, 0.0));
+        assert!(shape.contains(0.0, -100.0));
+        assert!(!shape.contains(200.0, 0.0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Drawing target of an art: a canvas in the page or an `OffscreenCanvas`
+//! handed to a worker.
+
+use crate::error::Error;
+use crate::helpers::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    Event, EventTarget, HtmlCanvasElement, OffscreenCanvas, WebGl2RenderingContext as GL,
+};
+
+/// Size of an art in CSS pixels together with the device pixel ratio the
+/// backing store is rendered at.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub css_width: f32,
+    pub css_height: f32,
+    pub pixel_ratio: f32,
+}
+
+impl Viewport {
+    pub fn new(css_width: f32, css_height: f32, pixel_ratio: f32) -> Self {
+        Viewport {
+            css_width: css_width.max(1.0),
+            css_height: css_height.max(1.0),
+            pixel_ratio: if pixel_ratio > 0.0 { pixel_ratio } else { 1.0 },
+        }
+    }
+
+    /// Width of the backing store in device pixels.
+    pub fn width(&self) -> u32 {
+        ((self.css_width * self.pixel_ratio).round() as u32).max(1)
+    }
+
+    /// Height of the backing store in device pixels.
+    pub fn height(&self) -> u32 {
+        ((self.css_height * self.pixel_ratio).round() as u32).max(1)
+    }
+}
+
+pub enum Surface {
+    Html(HtmlCanvasElement),
+    Offscreen(OffscreenCanvas),
+}
+
+impl Surface {
+    /// Sizes the backing store to `viewport`. The CSS box is left to the
+    /// page, so its layout can size the canvas.
+    pub fn set_size(&self, viewport: &Viewport) {
+        match self {
+            Surface::Html(canvas) => {
+                canvas.set_width(viewport.width());
+                canvas.set_height(viewport.height());
+            }
+            Surface::Offscreen(canvas) => {
+                canvas.set_width(viewport.width());
+                canvas.set_height(viewport.height());
+            }
+        }
+    }
+
+    /// Applies `viewport` to the backing store and the GL viewport.
+    pub fn resize(&self, gl: &GL, viewport: &Viewport) {
+        self.set_size(viewport);
+        gl.viewport(0, 0, viewport.width() as i32, viewport.height() as i32);
+    }
+
+    /// Sizes the backing store to `viewport` and returns its WebGL2 context.
+    pub fn webgl2_context(&self, viewport: &Viewport) -> Result<GL, Error> {
+        self.set_size(viewport);
+
+        let context = match self {
+            Surface::Html(canvas) => canvas.get_context("webgl2")?,
+            Surface::Offscreen(canvas) => canvas.get_context("webgl2")?,
+        };
+        context
+            .and_then(|context| context.dyn_into::<GL>().ok())
+            .ok_or(Error::NoWebGl2)
+    }
+
+    fn event_target(&self) -> EventTarget {
+        match self {
+            Surface::Html(canvas) => canvas.clone().into(),
+            Surface::Offscreen(canvas) => canvas.clone().into(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ContextState {
+    lost: Cell<bool>,
+    restored: Cell<bool>,
+}
+
+/// Follows `webglcontextlost` and `webglcontextrestored` on a surface, so an
+/// art can skip drawing while its context is gone and rebuild its GPU
+/// objects once it is back.
+pub struct ContextWatch {
+    target: EventTarget,
+    state: Rc<ContextState>,
+    on_lost: Closure<dyn FnMut(Event)>,
+    on_restored: Closure<dyn FnMut(Event)>,
+}
+
+impl ContextWatch {
+    pub fn new(surface: &Surface) -> Result<Self, Error> {
+        let target = surface.event_target();
+        let state = Rc::new(ContextState::default());
+
+        let lost_state = state.clone();
+        let on_lost = Closure::<dyn FnMut(Event)>::new(move |event: Event| {
+            // The browser only restores a context whose loss was prevented
+            event.prevent_default();
+            console_error!("WebGL context lost");
+            lost_state.lost.set(true);
+            lost_state.restored.set(false);
+        });
+
+        let restored_state = state.clone();
+        let on_restored = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            restored_state.restored.set(true);
+        });
+
+        target.add_event_listener_with_callback(
+            "webglcontextlost",
+            on_lost.as_ref().unchecked_ref(),
+        )?;
+        target.add_event_listener_with_callback(
+            "webglcontextrestored",
+            on_restored.as_ref().unchecked_ref(),
+        )?;
+
+        Ok(ContextWatch {
+            target,
+            state,
+            on_lost,
+            on_restored,
+        })
+    }
+
+    /// Whether the context is gone, including after a restore that
+    /// `take_restored` hasn't handed over yet.
+    pub fn is_lost(&self) -> bool {
+        self.state.lost.get()
+    }
+
+    /// Returns `true` once after the context comes back, at which point the
+    /// caller has to rebuild everything it created on it.
+    pub fn take_restored(&self) -> bool {
+        if !self.state.restored.replace(false) {
+            return false;
+        }
+        self.state.lost.set(false);
+        true
+    }
+}
+
+impl Drop for ContextWatch {
+    fn drop(&mut self) {
+        let _ = self.target.remove_event_listener_with_callback(
+            "webglcontextlost",
+            self.on_lost.as_ref().unchecked_ref(),
+        );
+        let _ = self.target.remove_event_listener_with_callback(
+            "webglcontextrestored",
+            self.on_restored.as_ref().unchecked_ref(),
+        );
+    }
+}
+
+impl From<HtmlCanvasElement> for Surface {
+    fn from(canvas: HtmlCanvasElement) -> Self {
+        Surface::Html(canvas)
+    }
+}
+
+impl From<OffscreenCanvas> for Surface {
+    fn from(canvas: OffscreenCanvas) -> Self {
+        Surface::Offscreen(canvas)
+    }
+}
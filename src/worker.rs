@@ -0,0 +1,155 @@
+//! Message protocol for running an art on an `OffscreenCanvas` inside a
+//! dedicated worker, see `www/worker.js`. Nothing here touches `window`.
+
+use crate::art::{build_art, ArtHandle};
+use crate::error::Error;
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::OffscreenCanvas;
+
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct ArtWorker {
+    art: Option<ArtHandle>,
+}
+
+#[wasm_bindgen]
+impl ArtWorker {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ArtWorker {
+        ArtWorker::default()
+    }
+
+    /// Handles one message posted to the worker:
+    ///
+    /// - `{ type: "init", art, canvas, width, height }` builds `art` on the
+    ///   transferred `OffscreenCanvas`, replacing any previous one
+    /// - `{ type: "draw", timestamp }` renders one frame, `timestamp` being
+    ///   an optional `performance.now()` reading
+    /// - `{ type: "resize", width, height, dpr }` with `width` and `height`
+    ///   in CSS pixels and an optional device pixel ratio
+    /// - `{ type: "color", r, g, b }`
+    /// - `{ type: "start", max_fps }` draws on the worker's own animation
+    ///   frames, with an optional frame rate cap, until `{ type: "stop" }`
+    /// - `{ type: "pause" }`, `{ type: "resume" }`
+    /// - `{ type: "seek", time }`
+    /// - `{ type: "reseed", seed }`
+    /// - `{ type: "pointer", event, x, y }` with `event` one of `"move"`,
+    ///   `"down"`, `"up"` or `"leave"`, and `x`, `y` in CSS pixels from the
+    ///   canvas' top left corner
+    /// - `{ type: "destroy" }`
+    pub fn handle_message(&mut self, message: &JsValue) -> Result<(), Error> {
+        let kind = get_string(message, "type")?;
+        match kind.as_str() {
+            "init" => {
+                self.destroy();
+                let id = get_string(message, "art")?;
+                let canvas = get(message, "canvas")?
+                    .dyn_into::<OffscreenCanvas>()
+                    .map_err(|_| invalid("`canvas` is not an OffscreenCanvas".to_string()))?;
+                let width = get_f64(message, "width")? as u32;
+                let height = get_f64(message, "height")? as u32;
+                self.art = Some(build_art(&id, canvas.into(), width, height)?);
+            }
+            "draw" => {
+                let timestamp = Reflect::get(message, &JsValue::from_str("timestamp"))
+                    .ok()
+                    .and_then(|timestamp| timestamp.as_f64());
+                self.art()?.draw(timestamp)?;
+            }
+            "resize" => {
+                let width = get_f64(message, "width")? as f32;
+                let height = get_f64(message, "height")? as f32;
+                let pixel_ratio = Reflect::get(message, &JsValue::from_str("dpr"))
+                    .ok()
+                    .and_then(|dpr| dpr.as_f64())
+                    .unwrap_or(1.0) as f32;
+                self.art()?.resize(width, height, pixel_ratio);
+            }
+            "color" => {
+                let r = get_f64(message, "r")? as f32;
+                let g = get_f64(message, "g")? as f32;
+                let b = get_f64(message, "b")? as f32;
+                self.art()?.set_color(r, g, b);
+            }
+            "start" => {
+                let max_fps = Reflect::get(message, &JsValue::from_str("max_fps"))
+                    .ok()
+                    .and_then(|max_fps| max_fps.as_f64())
+                    .unwrap_or(0.0);
+                let art = self.art()?;
+                art.set_max_fps(max_fps);
+                art.start();
+            }
+            "stop" => self.art()?.stop(),
+            "pause" => self.art()?.pause(),
+            "resume" => self.art()?.resume(),
+            "seek" => {
+                let time = get_f64(message, "time")?;
+                self.art()?.set_time(time);
+            }
+            "reseed" => {
+                let seed = get_f64(message, "seed")? as u32;
+                self.art()?.reseed(seed)?;
+            }
+            "pointer" => {
+                let event = get_string(message, "event")?;
+                if event == "leave" {
+                    self.art()?.pointer_leave();
+                    return Ok(());
+                }
+                let x = get_f64(message, "x")? as f32;
+                let y = get_f64(message, "y")? as f32;
+                let art = self.art()?;
+                match event.as_str() {
+                    "move" => art.pointer_move(x, y),
+                    "down" => art.pointer_down(x, y),
+                    "up" => art.pointer_up(x, y),
+                    other => return Err(invalid(format!("unknown pointer event `{}`", other))),
+                }
+            }
+            "destroy" => self.destroy(),
+            other => return Err(invalid(format!("unknown type `{}`", other))),
+        }
+        Ok(())
+    }
+}
+
+impl ArtWorker {
+    fn art(&mut self) -> Result<&mut ArtHandle, Error> {
+        self.art
+            .as_mut()
+            .ok_or_else(|| invalid("no art in worker, send an init message first".to_string()))
+    }
+
+    fn destroy(&mut self) {
+        if let Some(mut art) = self.art.take() {
+            art.destroy();
+        }
+    }
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidMessage { reason }
+}
+
+fn get(message: &JsValue, key: &str) -> Result<JsValue, Error> {
+    let value = Reflect::get(message, &JsValue::from_str(key))?;
+    if value.is_undefined() {
+        return Err(invalid(format!("missing `{}`", key)));
+    }
+    Ok(value)
+}
+
+fn get_string(message: &JsValue, key: &str) -> Result<String, Error> {
+    get(message, key)?
+        .as_string()
+        .ok_or_else(|| invalid(format!("`{}` is not a string", key)))
+}
+
+fn get_f64(message: &JsValue, key: &str) -> Result<f64, Error> {
+    get(message, key)?
+        .as_f64()
+        .ok_or_else(|| invalid(format!("`{}` is not a number", key)))
+}
@@ -1,67 +1,16 @@
-import init, {
-  JellyFish,
-  Nudibranch,
-  HeartController,
-  PlanetaryTimer,
-  ChristmasTree,
-  Golfed1,
-  Eclipse,
-} from './gagl_wasm.js';
-
-var animation_number = null;
+import init, { list_arts, create_art_with_canvas } from './gagl_wasm.js';
+
 var art = null;
-var arts = {
-  Jellyfish: {
-    class: JellyFish,
-    ref: 'https://x.com/yuruyurau/status/1865420201086636376',
-    text: null,
-    color: [0.0, 1.0, 1.0],
-  },
-  Nudibranch: {
-    class: Nudibranch,
-    ref: 'https://x.com/yuruyurau/status/1866142306119885279',
-    text: null,
-    color: [0.0, 1.0, 1.0],
-  },
-  Heartbeat: {
-    class: HeartController,
-    ref: null,
-    text: '<span style="color: rgb(255, 0.0, 74);">For my dearest <strong>Ghasedak</strong></span>',
-    color: [1.0, 0.0, 0.29],
-  },
-  PlanetaryTimer: {
-    class: PlanetaryTimer,
-    ref: 'https://x.com/YoheiNishitsuji/status/1908486028018753622',
-    text: null,
-    color: [0.0, 0.0, 0.0],
-  },
-  ChristmasTree: {
-    class: ChristmasTree,
-    ref: 'https://x.com/YoheiNishitsuji/status/2004007970242547892',
-    text: null,
-    color: [1.0, 1.0, 1.0],
-  },
-  Golfed1: {
-    class: Golfed1,
-    ref: 'https://x.com/XorDev/status/2015813875833225715',
-    text: null,
-    color: [1.0, 1.0, 1.0],
-  },
-  Eclipse: {
-    class: Eclipse,
-    ref: 'https://x.com/XorDev/status/2016909652387123492',
-    text: null,
-    color: [1.0, 1.0, 1.0],
-  },
-};
+var arts = {};
 
 async function run(title) {
-  destroyAndRecreateCanvas('canvas_container', 'canvas');
-  let obj = arts[title]['class'];
+  const canvas = destroyAndRecreateCanvas('canvas_container', 'canvas');
   let color = arts[title]['color'];
 
-  if (arts[title]['ref']) {
-    document.getElementById('ref').setAttribute('href', arts[title]['ref']);
+  if (arts[title]['reference']) {
+    document
+      .getElementById('ref')
+      .setAttribute('href', arts[title]['reference']);
     document
       .getElementById('ref_line')
       .setAttribute('style', 'display: default;');
@@ -80,7 +29,22 @@ async function run(title) {
       .getElementById('text_line')
       .setAttribute('style', 'display: none;');
   }
-  art = new obj(400, 400);
+  // The art only sizes the backing store, the CSS box is up to the page
+  canvas.style.width = '400px';
+  canvas.style.height = '400px';
+  art = create_art_with_canvas(title, canvas, 400, 400);
+  art.resize(400, 400, window.devicePixelRatio || 1);
+
+  // Pointer and touch input, in CSS pixels from the canvas' top left corner
+  canvas.style.touchAction = 'none';
+  const at = (handler) => (e) => {
+    const rect = canvas.getBoundingClientRect();
+    handler(e.clientX - rect.left, e.clientY - rect.top);
+  };
+  canvas.addEventListener('pointermove', at((x, y) => art.pointer_move(x, y)));
+  canvas.addEventListener('pointerdown', at((x, y) => art.pointer_down(x, y)));
+  canvas.addEventListener('pointerup', at((x, y) => art.pointer_up(x, y)));
+  canvas.addEventListener('pointerleave', () => art.pointer_leave());
 
   const redSlider = document.getElementById('red');
   redSlider.value = color[0];
@@ -111,36 +75,19 @@ async function run(title) {
 
   updateColor();
 
-  function animate() {
-    art.draw();
-    animation_number = requestAnimationFrame(animate);
-  }
-
-  animate();
+  art.start();
 }
 
 function destroyAndRecreateCanvas(containerId, canvasId) {
   const oldCanvas = document.getElementById(canvasId);
   if (oldCanvas) {
-    if (animation_number) {
-      window.cancelAnimationFrame(animation_number);
-      animation_number = null;
-      try {
-        const gl =
-          oldCanvas.getContext('webgl2') || oldCanvas.getContext('webgl');
-        if (gl) {
-          gl.getExtension('WEBGL_lose_context')?.loseContext();
-
-          gl.useProgram(null);
-          gl.bindBuffer(gl.ARRAY_BUFFER, null);
-          gl.bindBuffer(gl.ELEMENT_ARRAY_BUFFER, null);
-          gl.bindVertexArray(null);
-        }
-      } catch (e) {}
-      if (art) {
-        art.destroy();
-        art = null;
-      }
+    if (art) {
+      // destroy deletes the art's GPU objects; losing the context as well
+      // frees the context slot before the canvas is garbage collected
+      art.destroy();
+      oldCanvas.getContext('webgl2')?.getExtension('WEBGL_lose_context')?.loseContext();
+      art.free();
+      art = null;
     }
     oldCanvas.remove();
   }
@@ -157,6 +104,11 @@ function destroyAndRecreateCanvas(containerId, canvasId) {
 }
 
 async function setup() {
+  await init();
+  list_arts().forEach((info) => {
+    arts[info.id] = info;
+  });
+
   const container = document.getElementById('arts');
   const keys = Object.keys(arts);
   keys.forEach((title) => {
@@ -175,7 +127,11 @@ async function setup() {
 
   window.addEventListener('hashchange', handleHash);
 
-  await init();
+  // Browser zoom and moving between screens change devicePixelRatio
+  window.addEventListener('resize', () => {
+    if (art) art.resize(400, 400, window.devicePixelRatio || 1);
+  });
+
   let title = window.location.hash.slice(1);
   if (!title || !keys.includes(title))
     title = keys[Math.floor(Math.random() * keys.length)];
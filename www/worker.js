@@ -0,0 +1,24 @@
+// Runs an art on an OffscreenCanvas off the main thread.
+//
+// const canvas = document.getElementById('canvas').transferControlToOffscreen();
+// const worker = new Worker('./worker.js', { type: 'module' });
+// worker.postMessage({ type: 'init', art: 'PlanetaryTimer', canvas, width: 400, height: 400 }, [canvas]);
+// worker.postMessage({ type: 'color', r: 1, g: 1, b: 1 });
+// worker.postMessage({ type: 'start', max_fps: 30 }); // or { type: 'draw', timestamp } per frame
+// worker.postMessage({ type: 'resize', width: 400, height: 400, dpr: devicePixelRatio });
+// worker.postMessage({ type: 'destroy' });
+//
+// Failures come back as { type: 'error', kind, message, ... }.
+import init, { ArtWorker } from './gagl_wasm.js';
+
+const ready = init().then(() => new ArtWorker());
+
+self.onmessage = async (event) => {
+  const worker = await ready;
+  try {
+    worker.handle_message(event.data);
+  } catch (e) {
+    // kind, log, name... are own properties of the error, message isn't
+    self.postMessage({ ...e, type: 'error', message: e?.message ?? String(e) });
+  }
+};